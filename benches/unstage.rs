@@ -0,0 +1,95 @@
+//! Unstaging a whole accidentally added `node_modules`: one `git reset` per
+//! file (the old way) against a single batched call.
+//!
+//! Run with `cargo bench --bench unstage`. Set `NOOB_COMMIT_BENCH_FILES` to
+//! change the repo size (default 50000). Spawning tens of thousands of
+//! processes takes minutes, so the per-file way is timed on a sample and
+//! extrapolated.
+
+use noob_commit::git;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Files unstaged one by one to estimate the per-file cost.
+const SAMPLE: usize = 300;
+
+fn run_git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("git is installed");
+    assert!(status.success(), "git {} failed", args.join(" "));
+}
+
+/// A repo with one commit and `files` freshly staged under `node_modules/`.
+fn synthetic_repo(files: usize) -> (PathBuf, Vec<String>) {
+    let dir = std::env::temp_dir().join(format!("noob-commit-bench-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+
+    run_git(&dir, &["init", "-q"]);
+    run_git(&dir, &["config", "user.email", "bench@example.com"]);
+    run_git(&dir, &["config", "user.name", "Bench"]);
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    run_git(&dir, &["add", "."]);
+    run_git(&dir, &["commit", "-q", "-m", "init"]);
+
+    let mut paths = Vec::with_capacity(files);
+    for i in 0..files {
+        let path = format!("node_modules/pkg{}/file {}.js", i / 100, i);
+        let full = dir.join(&path);
+        std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+        std::fs::write(full, format!("module.exports = {};\n", i)).unwrap();
+        paths.push(path);
+    }
+    run_git(&dir, &["add", "."]);
+
+    (dir, paths)
+}
+
+fn main() {
+    let files = std::env::var("NOOB_COMMIT_BENCH_FILES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(50_000);
+
+    println!("🏗️  Building a repo with {} staged files...", files);
+    let (dir, paths) = synthetic_repo(files);
+    std::env::set_current_dir(&dir).unwrap();
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+    let sample = &paths[..SAMPLE.min(paths.len())];
+    let start = Instant::now();
+    for path in sample {
+        run_git(&dir, &["reset", "-q", "HEAD", "--", path]);
+    }
+    let per_file = start.elapsed() / sample.len() as u32;
+    let one_by_one = per_file * paths.len() as u32;
+
+    let start = Instant::now();
+    git::unstage(&paths[sample.len()..]).unwrap();
+    let batched = start.elapsed();
+
+    let staged = Command::new("git")
+        .args(["diff", "--cached", "--name-only"])
+        .output()
+        .unwrap();
+    assert!(staged.stdout.is_empty(), "everything should be unstaged");
+
+    println!(
+        "🐢 one process per file: {:?} ({:?} each, measured on {} files)",
+        one_by_one,
+        per_file,
+        sample.len()
+    );
+    println!("🚀 one batched call:     {:?}", batched);
+    println!(
+        "⚡ {:.0}x faster",
+        one_by_one.as_secs_f64() / batched.max(Duration::from_micros(1)).as_secs_f64()
+    );
+
+    std::env::set_current_dir(std::env::temp_dir()).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -1,8 +1,8 @@
-/// Basic usage example for noob-commit
-/// 
-/// This example demonstrates how to use noob-commit programmatically
-/// 
-/// Run with: cargo run --example basic_usage
+//! Basic usage example for noob-commit
+//! 
+//! This example demonstrates how to use noob-commit programmatically
+//! 
+//! Run with: cargo run --example basic_usage
 
 use noob_commit::Commit;
 use std::env;
@@ -16,7 +16,7 @@ fn main() {
 
     println!("Example commit title: {}", commit.title);
     println!("Example commit description: {}", commit.description);
-    println!("\nFull commit message:\n{}", commit.to_string());
+    println!("\nFull commit message:\n{}", commit);
 
     // Example of checking for API key
     match env::var("OPENAI_API_KEY") {
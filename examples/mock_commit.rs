@@ -1,15 +1,15 @@
-/// Mock commit example for testing purposes
-/// 
-/// This example shows how to create mock commits for testing
-/// without actually making API calls
-/// 
-/// Run with: cargo run --example mock_commit
+//! Mock commit example for testing purposes
+//! 
+//! This example shows how to create mock commits for testing
+//! without actually making API calls
+//! 
+//! Run with: cargo run --example mock_commit
 
 use noob_commit::Commit;
 
 fn main() {
     // Example commit messages for different scenarios
-    let commit_examples = vec![
+    let commit_examples = [
         Commit::new(
             "feat: add user authentication system".to_string(),
             "Implemented OAuth2 authentication with support for Google and GitHub providers. Added JWT token generation and validation for secure session management.".to_string(),
@@ -0,0 +1,216 @@
+//! `.noob-commit-allow`: saying "this one is fine" without turning off every
+//! protection with `--ok-to-send-env`.
+//!
+//! ```text
+//! # fake keys for the parser tests
+//! tests/fixtures/
+//! # the example key in the docs
+//! fingerprint:9f2c0e5d7b1a4c3e
+//! ```
+//!
+//! Paths are gitignore-style globs, like the filter rules, and let through
+//! both filtered files and secrets found in them. A fingerprint allows one
+//! secret in one file (see [`Finding::fingerprint`]). A single line can also
+//! allow itself with a `noob-commit:allow` comment.
+
+use crate::filters;
+use crate::secrets::Finding;
+use globset::GlobSet;
+use std::fmt;
+use std::path::Path;
+
+/// Name of the allowlist file, at the top of the worktree.
+pub const ALLOW_FILE: &str = ".noob-commit-allow";
+
+/// Marker that allows every finding on the line it's on.
+pub const INLINE_MARKER: &str = "noob-commit:allow";
+
+/// Prefix of a fingerprint entry.
+const FINGERPRINT: &str = "fingerprint:";
+
+/// One line of the allowlist file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Line number in the file.
+    pub line: usize,
+    /// The entry as written, e.g. `tests/fixtures/`.
+    pub text: String,
+}
+
+/// What let a finding or path through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allowed<'a> {
+    Inline,
+    Entry(&'a Entry),
+}
+
+impl fmt::Display for Allowed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Allowed::Inline => write!(f, "inline {}", INLINE_MARKER),
+            Allowed::Entry(entry) => write!(f, "{}:{} '{}'", ALLOW_FILE, entry.line, entry.text),
+        }
+    }
+}
+
+/// Every entry of the allowlist, with the path globs compiled.
+#[derive(Debug)]
+pub struct Allowlist {
+    paths: Vec<Entry>,
+    fingerprints: Vec<Entry>,
+    /// One glob per entry of `paths`, in the same order.
+    set: GlobSet,
+}
+
+impl Allowlist {
+    /// The allowlist in `root`, empty if there is none.
+    pub fn load(root: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(root.join(ALLOW_FILE)) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::parse(""),
+            Err(e) => Err(format!("{}: {}", ALLOW_FILE, e)),
+        }
+    }
+
+    /// One entry per line, `#` starts a comment line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        let mut fingerprints = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = Entry {
+                line: i + 1,
+                text: line.to_string(),
+            };
+            if line.starts_with(FINGERPRINT) {
+                fingerprints.push(entry);
+            } else {
+                paths.push(entry);
+            }
+        }
+
+        let set =
+            filters::compile(paths.iter().map(|entry| entry.text.as_str())).map_err(|(i, e)| {
+                format!(
+                    "bad pattern '{}' on line {} of {}: {}",
+                    paths[i].text, paths[i].line, ALLOW_FILE, e
+                )
+            })?;
+        Ok(Self {
+            paths,
+            fingerprints,
+            set,
+        })
+    }
+
+    /// The first path entry matching `path`.
+    pub fn path(&self, path: &str) -> Option<&Entry> {
+        self.set
+            .matches(path)
+            .into_iter()
+            .min()
+            .map(|i| &self.paths[i])
+    }
+
+    /// Why `finding` is fine, if it is: its own line says so, its
+    /// fingerprint is listed, or its file is.
+    pub fn finding(&self, finding: &Finding) -> Option<Allowed<'_>> {
+        if finding.allowed_inline {
+            return Some(Allowed::Inline);
+        }
+        let fingerprint = finding.fingerprint();
+        self.fingerprints
+            .iter()
+            .find(|entry| entry.text[FINGERPRINT.len()..].trim() == fingerprint)
+            .or_else(|| self.path(&finding.path))
+            .map(Allowed::Entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::SecretKind;
+
+    fn finding(path: &str, secret: &str) -> Finding {
+        Finding {
+            kind: SecretKind::HighEntropy,
+            path: path.to_string(),
+            line: 1,
+            secret: secret.to_string(),
+            allowed_inline: false,
+        }
+    }
+
+    #[test]
+    fn test_paths_and_fingerprints() {
+        let known = finding("docs/setup.md", "not-a-real-secret");
+        let allowlist = Allowlist::parse(&format!(
+            "# fixtures\ntests/fixtures/\n\n{}{}\n*.snap\n",
+            FINGERPRINT,
+            known.fingerprint()
+        ))
+        .unwrap();
+
+        let by_path = finding("tests/fixtures/keys.json", "anything");
+        assert_eq!(
+            allowlist.finding(&by_path).unwrap().to_string(),
+            ".noob-commit-allow:2 'tests/fixtures/'"
+        );
+        assert_eq!(
+            allowlist.finding(&known).unwrap().to_string(),
+            format!(".noob-commit-allow:4 'fingerprint:{}'", known.fingerprint())
+        );
+        assert_eq!(allowlist.path("ui/__snapshots__/a.snap").unwrap().line, 5);
+
+        // Same secret somewhere else is a different finding
+        assert_eq!(
+            allowlist.finding(&finding("src/main.rs", "not-a-real-secret")),
+            None
+        );
+        assert_eq!(allowlist.path("src/fixtures/keys.json"), None);
+    }
+
+    #[test]
+    fn test_repo_allows_exactly_its_fake_keys() {
+        let path = "tests/fixtures/fake_keys.rs";
+        let fixture = include_str!("../tests/fixtures/fake_keys.rs");
+        let mut diff = format!(
+            "diff --git a/{0} b/{0}\n--- /dev/null\n+++ b/{0}\n@@ -0,0 +1,{1} @@\n",
+            path,
+            fixture.lines().count()
+        );
+        for line in fixture.lines() {
+            diff.push_str(&format!("+{}\n", line));
+        }
+
+        let allowlist = Allowlist::parse(include_str!("../.noob-commit-allow")).unwrap();
+        let findings = crate::secrets::scan_diff(&diff);
+        assert_eq!(findings.len(), allowlist.fingerprints.len());
+        for finding in &findings {
+            assert!(
+                allowlist.finding(finding).is_some(),
+                "add fingerprint:{} ({} on line {}) to {}",
+                finding.fingerprint(),
+                finding.kind,
+                finding.line,
+                ALLOW_FILE
+            );
+        }
+    }
+
+    #[test]
+    fn test_inline_marker_and_bad_patterns() {
+        let mut marked = finding("src/main.rs", "whatever");
+        marked.allowed_inline = true;
+        let empty = Allowlist::parse("").unwrap();
+        assert_eq!(empty.finding(&marked), Some(Allowed::Inline));
+        assert_eq!(empty.finding(&finding("src/main.rs", "whatever")), None);
+
+        let err = Allowlist::parse("ok/\n[oops\n").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+    }
+}
@@ -0,0 +1,489 @@
+//! Fit a `git diff` into the AI's input budget without cutting it apart.
+//!
+//! The diff is split into files and hunks, and whole hunks (or whole files)
+//! are left out until it fits. Whatever was left out is listed in a manifest
+//! at the end so the model knows what it did not see.
+
+use std::fmt::Write;
+use std::path::Path;
+
+/// Longest piece handed to the tokenizer at once. BPE merging is quadratic
+/// on long runs without whitespace (minified files, base64 blobs...).
+const MAX_TOKENIZER_PIECE: usize = 512;
+
+/// Count tokens with the offline `o200k_base` tokenizer.
+///
+/// Exact for recent OpenAI models and a close enough estimate for the rest.
+/// Very long lines are counted in pieces, which can only overestimate.
+pub fn count_tokens(text: &str) -> usize {
+    let bpe = tiktoken_rs::o200k_base_singleton();
+    let mut total = 0;
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let mut end = rest.len().min(MAX_TOKENIZER_PIECE);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(newline) = rest[..end].find('\n') {
+            end = newline + 1;
+        }
+        total += bpe.encode_ordinary(&rest[..end]).len();
+        rest = &rest[end..];
+    }
+
+    total
+}
+
+/// One file section of a diff: the `diff --git` header and its hunks.
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub path: String,
+    /// Everything before the first `@@` (mode changes, `---`/`+++`, binary notes...).
+    pub header: String,
+    /// Each hunk starting at its `@@` line.
+    pub hunks: Vec<String>,
+}
+
+impl FileDiff {
+    /// Lock files, minified bundles and binaries say little about the change.
+    pub fn is_low_priority(&self) -> bool {
+        let filename = Path::new(&self.path)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or("");
+
+        matches!(
+            filename,
+            "Cargo.lock"
+                | "package-lock.json"
+                | "yarn.lock"
+                | "pnpm-lock.yaml"
+                | "poetry.lock"
+                | "Pipfile.lock"
+                | "Gemfile.lock"
+                | "composer.lock"
+                | "go.sum"
+                | "Podfile.lock"
+        ) || filename.ends_with(".min.js")
+            || filename.ends_with(".min.css")
+            || filename.ends_with(".map")
+            || filename.ends_with(".snap")
+            || filename.ends_with(".svg")
+            || self.header.contains("\nBinary files ")
+    }
+}
+
+/// Split a unified diff into per-file sections.
+pub fn parse_diff(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            files.push(FileDiff {
+                path: path_from_diff_line(line),
+                header: line.to_string(),
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            // Anything before the first file header is not part of a file
+            continue;
+        };
+
+        if line.starts_with("@@") {
+            file.hunks.push(line.to_string());
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.push_str(line);
+        } else {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                if let Some(path) = unquote(path.trim_end()).strip_prefix("b/") {
+                    file.path = path.to_string();
+                }
+            }
+            file.header.push_str(line);
+        }
+    }
+
+    files
+}
+
+fn path_from_diff_line(line: &str) -> String {
+    let rest = line.trim_start_matches("diff --git ").trim_end();
+    let start = if rest.ends_with('"') {
+        rest.rfind(" \"b/")
+    } else {
+        rest.rfind(" b/")
+    };
+    match start {
+        Some(idx) => unquote(&rest[idx + 1..])[2..].to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// A path as git writes it in diff headers: as is, or in double quotes with
+/// C-style escapes when it has non-ASCII bytes (octal, with the default
+/// `core.quotePath`), quotes, backslashes or control characters.
+fn unquote(path: &str) -> String {
+    let Some(quoted) = path
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return path.to_string();
+    };
+
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('a') => 0x07,
+            Some('b') => 0x08,
+            Some('t') => b'\t',
+            Some('n') => b'\n',
+            Some('v') => 0x0b,
+            Some('f') => 0x0c,
+            Some('r') => b'\r',
+            Some(digit @ '0'..='7') => {
+                let octal: String = std::iter::once(digit)
+                    .chain(chars.by_ref().take(2))
+                    .collect();
+                u8::from_str_radix(&octal, 8).unwrap_or(b'?')
+            }
+            Some(other) => other as u8,
+            None => b'\\',
+        };
+        bytes.push(byte);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Something that didn't make it into the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Omission {
+    pub path: String,
+    pub hunks_dropped: usize,
+    pub hunks_total: usize,
+    /// The file header itself didn't fit, so the file is missing entirely.
+    pub whole_file: bool,
+}
+
+/// The diff that will actually be sent, plus what was left out.
+#[derive(Debug, Clone)]
+pub struct BudgetedDiff {
+    pub text: String,
+    pub tokens: usize,
+    pub omitted: Vec<Omission>,
+}
+
+impl BudgetedDiff {
+    pub fn is_trimmed(&self) -> bool {
+        !self.omitted.is_empty()
+    }
+}
+
+/// Limits for the diff sent to the AI, in tokens and in characters (not
+/// bytes). `0` means unlimited.
+#[derive(Debug, Clone, Copy)]
+pub struct DiffBudget {
+    pub max_tokens: usize,
+    pub max_chars: usize,
+}
+
+/// Room kept for the manifest of omitted files.
+const MANIFEST_RESERVE_TOKENS: usize = 200;
+const MANIFEST_RESERVE_CHARS: usize = 800;
+
+impl DiffBudget {
+    pub fn new(max_tokens: usize, max_chars: usize) -> Self {
+        Self {
+            max_tokens,
+            max_chars,
+        }
+    }
+
+    pub(crate) fn fits(&self, tokens: usize, chars: usize) -> bool {
+        (self.max_tokens == 0 || tokens <= self.max_tokens)
+            && (self.max_chars == 0 || chars <= self.max_chars)
+    }
+
+    /// Whether `diff` can be sent as is.
+    pub fn fits_text(&self, diff: &str) -> bool {
+        let chars = char_count(self.max_chars, diff);
+        // Skip tokenizing when the character limit already says no
+        self.fits(0, chars) && self.fits(count_tokens(diff), chars)
+    }
+
+    /// Trim `diff` to the budget, dropping whole hunks and files.
+    pub fn apply(&self, diff: &str) -> BudgetedDiff {
+        if self.fits_text(diff) {
+            return BudgetedDiff {
+                text: diff.to_string(),
+                tokens: count_tokens(diff),
+                omitted: Vec::new(),
+            };
+        }
+
+        let files = parse_diff(diff);
+        let limits = DiffBudget::new(
+            reserve(self.max_tokens, MANIFEST_RESERVE_TOKENS),
+            reserve(self.max_chars, MANIFEST_RESERVE_CHARS),
+        );
+
+        // Hand out the budget to important and small files first so a single
+        // giant file can't crowd out everything else
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        order.sort_by_key(|&i| {
+            (
+                files[i].is_low_priority(),
+                files[i].header.len() + files[i].hunks.iter().map(String::len).sum::<usize>(),
+            )
+        });
+
+        let mut kept: Vec<Option<Vec<bool>>> = vec![None; files.len()];
+        let (mut tokens, mut chars) = (0, 0);
+
+        for i in order {
+            let file = &files[i];
+            let header_tokens = count_tokens(&file.header);
+            let header_chars = char_count(limits.max_chars, &file.header);
+            if !limits.fits(tokens + header_tokens, chars + header_chars) {
+                continue;
+            }
+            tokens += header_tokens;
+            chars += header_chars;
+
+            let mut hunks_kept = Vec::with_capacity(file.hunks.len());
+            for hunk in &file.hunks {
+                let hunk_tokens = count_tokens(hunk);
+                let hunk_chars = char_count(limits.max_chars, hunk);
+                let fits = limits.fits(tokens + hunk_tokens, chars + hunk_chars);
+                if fits {
+                    tokens += hunk_tokens;
+                    chars += hunk_chars;
+                }
+                hunks_kept.push(fits);
+            }
+            kept[i] = Some(hunks_kept);
+        }
+
+        let mut text = String::new();
+        let mut omitted = Vec::new();
+        for (file, kept) in files.iter().zip(kept) {
+            match kept {
+                Some(hunks_kept) => {
+                    text.push_str(&file.header);
+                    for (hunk, keep) in file.hunks.iter().zip(&hunks_kept) {
+                        if *keep {
+                            text.push_str(hunk);
+                        }
+                    }
+                    let dropped = hunks_kept.iter().filter(|k| !**k).count();
+                    if dropped > 0 {
+                        omitted.push(Omission {
+                            path: file.path.clone(),
+                            hunks_dropped: dropped,
+                            hunks_total: file.hunks.len(),
+                            whole_file: false,
+                        });
+                    }
+                }
+                None => omitted.push(Omission {
+                    path: file.path.clone(),
+                    hunks_dropped: file.hunks.len(),
+                    hunks_total: file.hunks.len(),
+                    whole_file: true,
+                }),
+            }
+        }
+
+        text.push_str(&manifest(&omitted));
+        BudgetedDiff {
+            tokens: count_tokens(&text),
+            text,
+            omitted,
+        }
+    }
+}
+
+/// Characters in `text`, or `0` without a character limit to check against.
+pub(crate) fn char_count(max_chars: usize, text: &str) -> usize {
+    if max_chars == 0 {
+        0
+    } else {
+        text.chars().count()
+    }
+}
+
+/// Shrink `limit` to leave room for the manifest, never turning a real limit
+/// into `0` (unlimited).
+fn reserve(limit: usize, room: usize) -> usize {
+    limit - room.min(limit / 4)
+}
+
+/// Tell the model which parts of the change it did not get to see.
+fn manifest(omitted: &[Omission]) -> String {
+    let mut out = String::from(
+        "\n... (diff trimmed to fit the size limit)\nThese changes were left out, mention them only by name:\n",
+    );
+    for o in omitted {
+        if o.whole_file {
+            let _ = writeln!(out, "- {} (whole file)", o.path);
+        } else {
+            let _ = writeln!(
+                out,
+                "- {} ({} of {} hunks)",
+                o.path, o.hunks_dropped, o.hunks_total
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, hunks: &[&str]) -> String {
+        let mut out = format!(
+            "diff --git a/{path} b/{path}\nindex 111..222 100644\n--- a/{path}\n+++ b/{path}\n"
+        );
+        for (i, body) in hunks.iter().enumerate() {
+            out.push_str(&format!("@@ -{0},1 +{0},1 @@\n{1}\n", i + 1, body));
+        }
+        out
+    }
+
+    #[test]
+    fn test_parse_diff_files_and_hunks() {
+        let diff = file("src/a.rs", &["+a", "+b"]) + &file("with space.txt", &["+c"]);
+        let files = parse_diff(&diff);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/a.rs");
+        assert_eq!(files[0].hunks.len(), 2);
+        assert!(files[0].header.ends_with("+++ b/src/a.rs\n"));
+        assert_eq!(files[1].path, "with space.txt");
+        assert_eq!(files[1].hunks, vec!["@@ -1,1 +1,1 @@\n+c\n"]);
+    }
+
+    #[test]
+    fn test_parse_diff_unquotes_paths() {
+        // What git writes for `naïve "notes".txt` and a new `🤡.md`
+        let diff = "diff --git \"a/na\\303\\257ve \\\"notes\\\".txt\" \"b/na\\303\\257ve \\\"notes\\\".txt\"\n\
+            index 111..222 100644\n\
+            --- \"a/na\\303\\257ve \\\"notes\\\".txt\"\n\
+            +++ \"b/na\\303\\257ve \\\"notes\\\".txt\"\n\
+            @@ -1 +1 @@\n\
+            +x\n\
+            diff --git \"a/\\360\\237\\244\\241.md\" \"b/\\360\\237\\244\\241.md\"\n\
+            new file mode 100644\n";
+        let files = parse_diff(diff);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "naïve \"notes\".txt");
+        assert_eq!(files[1].path, "🤡.md");
+        assert_eq!(unquote("plain/path.rs"), "plain/path.rs");
+        assert_eq!(unquote("\"tab\\there\""), "tab\there");
+    }
+
+    #[test]
+    fn test_small_diff_untouched() {
+        let diff = file("a.rs", &["+hello"]);
+        let result = DiffBudget::new(1000, 0).apply(&diff);
+        assert_eq!(result.text, diff);
+        assert!(!result.is_trimmed());
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let diff = file("a.rs", &[&"+x".repeat(10_000)]);
+        let result = DiffBudget::new(0, 0).apply(&diff);
+        assert_eq!(result.text, diff);
+    }
+
+    #[test]
+    fn test_never_splits_utf8_or_hunks() {
+        // Emoji and accents everywhere: byte truncation would panic here
+        let big = "+olá 🤡 ação ".repeat(400);
+        let diff = file("src/a.rs", &["+small", &big]) + &file("b.rs", &["+tiny"]);
+        let result = DiffBudget::new(0, 1200).apply(&diff);
+
+        assert!(result.is_trimmed());
+        assert!(result.text.contains("+small\n"));
+        assert!(result.text.contains("+tiny\n"));
+        assert!(!result.text.contains("🤡"));
+        assert!(result.text.contains("- src/a.rs (1 of 2 hunks)"));
+    }
+
+    #[test]
+    fn test_char_limit_counts_characters_not_bytes() {
+        // 300 characters, but 1200 bytes
+        let diff = file("a.rs", &[&"🤡".repeat(300)]);
+        let chars = diff.chars().count();
+        assert!(diff.len() > chars + 800);
+
+        assert!(DiffBudget::new(0, chars).fits_text(&diff));
+        assert!(!DiffBudget::new(0, chars - 1).fits_text(&diff));
+        assert!(!DiffBudget::new(0, chars).apply(&diff).is_trimmed());
+    }
+
+    #[test]
+    fn test_drops_low_priority_files_first() {
+        let lock = "+name = \"dep\"\n".repeat(300);
+        let code = "+fn important() {}\n".repeat(50);
+        let diff = file("Cargo.lock", &[&lock]) + &file("src/main.rs", &[&code]);
+        let result = DiffBudget::new(count_tokens(&code) + 300, 0).apply(&diff);
+
+        assert!(result.text.contains("fn important"));
+        assert!(!result.text.contains("name = \"dep\""));
+        assert_eq!(result.omitted.len(), 1);
+        assert_eq!(result.omitted[0].path, "Cargo.lock");
+        // The header still fit, so the model knows the lock file changed
+        assert!(result.text.contains("- Cargo.lock (1 of 1 hunks)"));
+    }
+
+    #[test]
+    fn test_manifest_lists_whole_files() {
+        let diff = file("a.rs", &["+a"]) + &file("b.rs", &[&"+b".repeat(1000)]);
+        let result = DiffBudget::new(0, 120).apply(&diff);
+        assert!(result.text.contains("+a\n"));
+
+        assert!(result.is_trimmed());
+        assert!(result.omitted.iter().any(|o| o.whole_file));
+        assert!(result.text.contains("(whole file)"));
+    }
+
+    #[test]
+    fn test_tiny_limit_is_not_unlimited() {
+        let diff = file("a.rs", &[&"+x".repeat(1000)]);
+        let result = DiffBudget::new(0, 100).apply(&diff);
+        assert!(result.is_trimmed());
+        assert!(!result.text.contains("+xx"));
+    }
+
+    #[test]
+    fn test_count_tokens() {
+        assert_eq!(count_tokens(""), 0);
+        assert!(count_tokens("fn main() { println!(\"hi\"); }") > 5);
+        assert_eq!(count_tokens("hello world\n"), 3);
+    }
+
+    #[test]
+    fn test_count_tokens_long_lines() {
+        // Would take minutes if handed to the tokenizer in one piece
+        let blob = "a".repeat(200_000);
+        let tokens = count_tokens(&blob);
+        assert!(tokens > 0 && tokens <= blob.len());
+
+        // Multi-byte characters straddling a piece boundary must not panic
+        let emoji = "🤡".repeat(1000);
+        assert!(count_tokens(&emoji) > 0);
+    }
+}
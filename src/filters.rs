@@ -0,0 +1,573 @@
+//! Which changed files never get staged: secrets, dependency folders and
+//! build junk.
+//!
+//! Rules are gitignore-style globs. The built-in lists come first, then the
+//! global config, then the repo's `.noob-commit.toml`; within a category the
+//! last matching rule wins, so a later `allow` can carve out an exception
+//! from an earlier `deny` and vice versa.
+//!
+//! ```toml
+//! # .noob-commit.toml
+//! staged_only = true              # same as --staged-only, unless the flag says otherwise
+//!
+//! [modules]
+//! allow = ["vendor/", "build/"]   # we vendor Go deps and have a real build/
+//!
+//! [security]
+//! deny = ["*.pem"]
+//! allow = [".env.example"]
+//!
+//! [crap]
+//! defaults = false                # drop the built-in list entirely
+//! deny = ["*.log"]
+//!
+//! [redact]                        # never sent to the AI, see secrets::Redactor
+//! patterns = ['[a-z0-9-]+\.corp\.example\.com']
+//! ```
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Name of the per-repo config file, at the top of the worktree.
+pub const REPO_CONFIG: &str = ".noob-commit.toml";
+
+/// Exact secret file names, wherever they live.
+const SECURITY: &[&str] = &[
+    ".env",
+    ".env.local",
+    ".env.production",
+    ".env.development",
+    ".env.test",
+    ".env.staging",
+    ".env.*.local",
+    ".npmrc",
+    ".pypirc",
+    "credentials",
+    "secrets.yml",
+    "secrets.yaml",
+    "id_rsa",
+    "id_ed25519",
+    "id_ecdsa",
+    "id_dsa",
+];
+
+/// Dependency and virtualenv folders, wherever they live.
+const MODULES: &[&str] = &[
+    "node_modules/",
+    "venv/",
+    ".venv/",
+    "env/",
+    "virtualenv/",
+    ".virtualenv/",
+    "vendor/",
+    "bower_components/",
+    "jspm_packages/",
+    ".npm/",
+    ".yarn/",
+    ".pnpm-store/",
+    "pip-wheel-metadata/",
+    ".tox/",
+    ".nox/",
+    ".hypothesis/",
+    ".pytest_cache/",
+    "htmlcov/",
+    ".coverage", // coverage.py's data file
+    ".coverage/",
+    "target/",  // Rust
+    "Pods/",    // iOS
+    ".gradle/", // Android
+    "build/",   // Various build systems
+    "dist/",    // Distribution folders
+];
+
+/// Caches, editor droppings and compiled output. `!` marks an exception.
+const CRAP: &[&str] = &[
+    ".DS_Store",
+    "Thumbs.db",
+    "desktop.ini",
+    ".gitkeep",
+    ".keep",
+    // Editor temp and backup files
+    "*.swp",
+    "*.swo",
+    "*.swn",
+    "*.log",
+    "*.tmp",
+    "*.temp",
+    "*.cache",
+    "*.bak",
+    "*.backup",
+    "*.old",
+    "*.orig",
+    "*~",
+    // Python bytecode, unless it oddly sits in sources or docs
+    "*.{pyc,pyo,pyd}",
+    "!*/**/{src,docs}/**/*.{pyc,pyo,pyd}",
+    "*cpython*.{pyc,pyo,pyd}",
+    "**/{build,dist,.eggs,wheelhouse}/**/*.{pyc,pyo,pyd}",
+    // Native libraries only when they look like build output
+    "**/{build,dist,target,.libs}/**/*.{so,dylib,dll}",
+    "*.class",
+    "**/{lib,libs,vendor,dependencies}/**/*.jar",
+    "*.o",
+    "*.a",
+    "**/{build,dist,target,bin,Debug,Release}/**/*.exe",
+    "*.idb",
+    "*.pdb",
+    "*.sage",
+    "*.egg-info",
+    "*.egg-info/",
+    // Cache directories
+    "__pycache__/",
+    ".pytest_cache/",
+    ".mypy_cache/",
+    ".ruff_cache/",
+    ".sass-cache/",
+    ".cache/",
+    ".parcel-cache/",
+    ".next/",
+    ".nuxt/",
+    ".docusaurus/",
+    ".serverless/",
+    ".fusebox/",
+    ".dynamodb/",
+    ".tern-port",
+    ".yarn-integrity",
+];
+
+/// Kind of file kept out of commits, each with its own escape hatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Security,
+    Modules,
+    Crap,
+}
+
+impl Category {
+    pub const ALL: [Category; 3] = [Category::Security, Category::Modules, Category::Crap];
+
+    /// Table name in the config file.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Category::Security => "security",
+            Category::Modules => "modules",
+            Category::Crap => "crap",
+        }
+    }
+
+    /// The command line flag that lets these files through.
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Category::Security => "--ok-to-send-env",
+            Category::Modules => "--yes-to-modules",
+            Category::Crap => "--yes-to-crap",
+        }
+    }
+
+    fn builtin(&self) -> &'static [&'static str] {
+        match self {
+            Category::Security => SECURITY,
+            Category::Modules => MODULES,
+            Category::Crap => CRAP,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::Security => "security file",
+            Category::Modules => "dependency/module folder",
+            Category::Crap => "cache/build artifact",
+        })
+    }
+}
+
+/// Where a rule came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Builtin,
+    File(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Builtin => f.write_str("built-in defaults"),
+            Source::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// One allow or deny pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub category: Category,
+    pub pattern: String,
+    pub allow: bool,
+    pub source: Source,
+}
+
+/// Rules for one category in a config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CategoryConfig {
+    /// Keep the built-in list (default true).
+    pub defaults: Option<bool>,
+    pub deny: Vec<String>,
+    pub allow: Vec<String>,
+}
+
+/// Extra things to hide from the AI, on top of the detected secrets.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Regexes matched line by line; with a capture group only the group is
+    /// redacted.
+    pub patterns: Vec<String>,
+}
+
+/// A `.noob-commit.toml` or global config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Default for `--staged-only`.
+    pub staged_only: Option<bool>,
+    pub security: CategoryConfig,
+    pub modules: CategoryConfig,
+    pub crap: CategoryConfig,
+    pub redact: RedactConfig,
+}
+
+impl FilterConfig {
+    /// Read `path`, or nothing if it doesn't exist.
+    pub fn read(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)
+                .map(Some)
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    fn category(&self, category: Category) -> &CategoryConfig {
+        match category {
+            Category::Security => &self.security,
+            Category::Modules => &self.modules,
+            Category::Crap => &self.crap,
+        }
+    }
+}
+
+/// Where the global config lives: `$XDG_CONFIG_HOME/noob-commit/config.toml`,
+/// falling back to `~/.config`.
+pub fn global_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("noob-commit").join("config.toml"))
+}
+
+/// The global config, then `.noob-commit.toml` in `root`, whichever exist.
+pub fn load_configs(root: &Path) -> Result<Vec<(Source, FilterConfig)>, String> {
+    let mut configs = Vec::new();
+    for path in global_config_path()
+        .into_iter()
+        .chain([root.join(REPO_CONFIG)])
+    {
+        if let Some(config) = FilterConfig::read(&path)? {
+            configs.push((Source::File(path), config));
+        }
+    }
+    Ok(configs)
+}
+
+/// Every rule compiled into a single [`GlobSet`].
+pub struct Filters {
+    rules: Vec<Rule>,
+    /// One glob per rule, in the same order.
+    set: GlobSet,
+}
+
+impl Filters {
+    /// Built-in rules, then the global config, then `.noob-commit.toml` in
+    /// `root`.
+    pub fn load(root: &Path) -> Result<Self, String> {
+        Self::new(&load_configs(root)?)
+    }
+
+    /// Built-in rules extended or overridden by `configs`, in order.
+    pub fn new(configs: &[(Source, FilterConfig)]) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for category in Category::ALL {
+            let keep_defaults = configs
+                .iter()
+                .all(|(_, config)| config.category(category).defaults != Some(false));
+            if keep_defaults {
+                for pattern in category.builtin() {
+                    let (pattern, allow) = match pattern.strip_prefix('!') {
+                        Some(pattern) => (pattern, true),
+                        None => (*pattern, false),
+                    };
+                    rules.push(Rule {
+                        category,
+                        pattern: pattern.to_string(),
+                        allow,
+                        source: Source::Builtin,
+                    });
+                }
+            }
+
+            for (source, config) in configs {
+                let config = config.category(category);
+                // Allows after denies, so a file's own exceptions win
+                let denies = config.deny.iter().map(|p| (p, false));
+                let allows = config.allow.iter().map(|p| (p, true));
+                for (pattern, allow) in denies.chain(allows) {
+                    rules.push(Rule {
+                        category,
+                        pattern: pattern.clone(),
+                        allow,
+                        source: source.clone(),
+                    });
+                }
+            }
+        }
+
+        let set = compile(rules.iter().map(|rule| rule.pattern.as_str())).map_err(|(i, e)| {
+            let rule = &rules[i];
+            format!("bad pattern '{}' in {}: {}", rule.pattern, rule.source, e)
+        })?;
+
+        Ok(Self { rules, set })
+    }
+
+    /// The rule that decides `path` in each category it matches, whether it
+    /// allows or denies.
+    pub fn explain(&self, path: &str) -> Vec<&Rule> {
+        let mut deciding: Vec<Option<usize>> = vec![None; Category::ALL.len()];
+        for i in self.set.matches(path) {
+            let slot = &mut deciding[category_index(self.rules[i].category)];
+            *slot = Some(slot.map_or(i, |j| j.max(i)));
+        }
+        deciding
+            .into_iter()
+            .flatten()
+            .map(|i| &self.rules[i])
+            .collect()
+    }
+
+    /// The deny rule that keeps `path` out, skipping categories for which
+    /// `allowed` is true (i.e. the user passed the escape hatch flag).
+    pub fn blocking(&self, path: &str, allowed: impl Fn(Category) -> bool) -> Option<&Rule> {
+        self.explain(path)
+            .into_iter()
+            .find(|rule| !rule.allow && !allowed(rule.category))
+    }
+
+    /// Every rule, in the order they apply.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+}
+
+fn category_index(category: Category) -> usize {
+    Category::ALL.iter().position(|c| *c == category).unwrap()
+}
+
+/// Compile gitignore-style `patterns` into one set, glob `i` for pattern
+/// `i`; a bad pattern is reported by index.
+pub(crate) fn compile<'a>(
+    patterns: impl IntoIterator<Item = &'a str>,
+) -> Result<GlobSet, (usize, String)> {
+    let mut builder = GlobSetBuilder::new();
+    for (i, pattern) in patterns.into_iter().enumerate() {
+        let glob = GlobBuilder::new(&expand(pattern))
+            .literal_separator(true)
+            .build()
+            .map_err(|e| (i, e.to_string()))?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| (0, e.to_string()))
+}
+
+/// Turn a gitignore-style pattern into a glob over repo-relative file paths:
+/// without a slash it matches at any depth, a leading slash or one in the
+/// middle anchors it to the root, and a trailing slash matches what's inside
+/// a directory. Unlike gitignore, only a trailing slash reaches into
+/// directories, so `credentials` doesn't take a `src/credentials/` module
+/// with it.
+fn expand(pattern: &str) -> String {
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let base = if pattern.contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+
+    if dir_only {
+        format!("{}/**", base)
+    } else {
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked(filters: &Filters, path: &str) -> Option<Category> {
+        filters.blocking(path, |_| false).map(|rule| rule.category)
+    }
+
+    #[test]
+    fn test_builtin_security_files() {
+        let filters = Filters::new(&[]).unwrap();
+        for path in [
+            ".env",
+            "app/.env.production",
+            ".env.dev.local",
+            "keys/id_rsa",
+        ] {
+            assert_eq!(
+                blocked(&filters, path),
+                Some(Category::Security),
+                "{}",
+                path
+            );
+        }
+        for path in [
+            ".env.example",
+            "src/env.rs",
+            "id_rsa.pub",
+            "src/credentials/mod.rs",
+        ] {
+            assert_ne!(
+                blocked(&filters, path),
+                Some(Category::Security),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin_module_directories() {
+        let filters = Filters::new(&[]).unwrap();
+        for path in [
+            "node_modules/a/index.js",
+            "web/node_modules/x.js",
+            "target/debug/app",
+            ".coverage",
+            "py/.coverage/data",
+        ] {
+            assert_eq!(blocked(&filters, path), Some(Category::Modules), "{}", path);
+        }
+        assert_eq!(blocked(&filters, "src/builder.rs"), None);
+        assert_eq!(blocked(&filters, "src/target.rs"), None);
+    }
+
+    #[test]
+    fn test_builtin_crap_files() {
+        let filters = Filters::new(&[]).unwrap();
+        for path in [
+            ".DS_Store",
+            "notes.txt~",
+            "app/__pycache__/mod.cpython-311.pyc",
+            "stray.pyc",
+            "src/foo.pyc",
+            "Main.class",
+            "pkg.egg-info/PKG-INFO",
+            "web/.next/cache.json",
+        ] {
+            assert_eq!(blocked(&filters, path), Some(Category::Crap), "{}", path);
+        }
+        // Bytecode that oddly lives in sources is left alone
+        assert_eq!(blocked(&filters, "pkg/src/fixture.pyc"), None);
+        // Shared libraries outside build output are real files
+        assert_eq!(blocked(&filters, "lib/libfoo.so"), None);
+        // File-name rules don't reach into directories of the same name
+        assert_eq!(blocked(&filters, "web/page.cache/index.html"), None);
+        assert_eq!(blocked(&filters, "src/main.rs"), None);
+    }
+
+    fn config(text: &str) -> (Source, FilterConfig) {
+        (
+            Source::File(PathBuf::from(REPO_CONFIG)),
+            toml::from_str(text).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_config_allow_overrides_builtin() {
+        let filters =
+            Filters::new(&[config("[modules]\nallow = [\"vendor/\", \"/build/\"]\n")]).unwrap();
+
+        assert_eq!(blocked(&filters, "vendor/github.com/x/y.go"), None);
+        assert_eq!(blocked(&filters, "build/mod.rs"), None);
+        // Anchored: only the top-level build/ is allowed
+        assert_eq!(
+            blocked(&filters, "web/build/app.js"),
+            Some(Category::Modules)
+        );
+
+        let rule = filters.explain("vendor/a.go")[0];
+        assert!(rule.allow);
+        assert_eq!(rule.source, Source::File(PathBuf::from(REPO_CONFIG)));
+    }
+
+    #[test]
+    fn test_config_deny_extends_and_defaults_off() {
+        let filters = Filters::new(&[config(
+            "[security]\ndeny = [\"*.pem\"]\n[crap]\ndefaults = false\n",
+        )])
+        .unwrap();
+
+        assert_eq!(
+            blocked(&filters, "certs/server.pem"),
+            Some(Category::Security)
+        );
+        assert_eq!(blocked(&filters, ".env"), Some(Category::Security));
+        assert_eq!(blocked(&filters, ".DS_Store"), None);
+    }
+
+    #[test]
+    fn test_later_config_wins() {
+        let global = (
+            Source::File(PathBuf::from("global.toml")),
+            toml::from_str("[security]\nallow = [\".npmrc\"]\n").unwrap(),
+        );
+        let repo = config("[security]\ndeny = [\".npmrc\"]\n");
+        let filters = Filters::new(&[global, repo]).unwrap();
+
+        let rule = filters.blocking(".npmrc", |_| false).unwrap();
+        assert_eq!(rule.source, Source::File(PathBuf::from(REPO_CONFIG)));
+    }
+
+    #[test]
+    fn test_escape_hatch_skips_category() {
+        let filters = Filters::new(&[]).unwrap();
+        assert!(filters
+            .blocking(".env", |c| c == Category::Security)
+            .is_none());
+    }
+
+    #[test]
+    fn test_staged_only_default() {
+        let (_, config) = config("staged_only = true\n\n[crap]\ndeny = [\"*.log\"]\n");
+        assert_eq!(config.staged_only, Some(true));
+        assert_eq!(FilterConfig::default().staged_only, None);
+    }
+
+    #[test]
+    fn test_bad_config_is_an_error() {
+        assert!(toml::from_str::<FilterConfig>("[modules]\nalow = []\n").is_err());
+        let bad = config("[crap]\ndeny = [\"[oops\"]\n");
+        assert!(Filters::new(&[bad]).is_err());
+    }
+}
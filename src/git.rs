@@ -0,0 +1,581 @@
+//! Thin wrappers around the `git` CLI.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// The tree with nothing in it, which is what an unborn branch is diffed
+/// against.
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// The all-zero object id.
+const NULL_OID: &str = "0000000000000000000000000000000000000000";
+
+/// The scratch index of a `--dry-run`, see [`IndexGuard::scratch`].
+static SCRATCH_INDEX: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// A git command, pointed at the scratch index when there is one.
+fn command() -> Command {
+    let mut command = Command::new("git");
+    if let Some(index) = &*SCRATCH_INDEX.lock().unwrap_or_else(|e| e.into_inner()) {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    command
+}
+
+/// Run git with `args` and return trimmed stdout, or stderr on failure.
+fn git(args: &[&str]) -> Result<String, String> {
+    git_raw(args).map(|out| out.trim().to_string())
+}
+
+/// Like [`git`], but stdout is returned untouched, for diffs and `-z` output
+/// where leading or trailing whitespace can be part of a path.
+fn git_raw(args: &[&str]) -> Result<String, String> {
+    let output = command()
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git {}: {}", args.join(" "), e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Absolute path of the top of the current worktree.
+pub fn toplevel() -> Result<PathBuf, String> {
+    git(&["rev-parse", "--show-toplevel"]).map(PathBuf::from)
+}
+
+/// Where the current directory sits inside the worktree, e.g. `src/` (empty
+/// at the top).
+pub fn prefix() -> Result<String, String> {
+    git(&["rev-parse", "--show-prefix"])
+}
+
+/// Where `name` lives inside the git directory, e.g. `info/exclude`, as
+/// seen from the current directory.
+pub fn git_path(name: &str) -> Result<PathBuf, String> {
+    git(&["rev-parse", "--git-path", name]).map(PathBuf::from)
+}
+
+/// Whether `HEAD` points at a commit. It doesn't right after `git init`.
+pub fn has_head() -> bool {
+    git(&["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok()
+}
+
+/// What the index is compared against: `HEAD`, or the empty tree before the
+/// first commit.
+fn diff_base() -> &'static str {
+    if has_head() {
+        "HEAD"
+    } else {
+        EMPTY_TREE
+    }
+}
+
+/// The diff of the index against `HEAD`, i.e. exactly what a commit would
+/// contain. Unstaged working tree changes are never included.
+pub fn staged_diff() -> Result<String, String> {
+    git_raw(&[
+        "diff",
+        "--cached",
+        "--no-color",
+        "--no-ext-diff",
+        diff_base(),
+    ])
+}
+
+/// Paths that differ between the index and `HEAD`.
+pub fn staged_files() -> Result<Vec<String>, String> {
+    let output = git_raw(&["diff", "--cached", "--name-only", "-z", diff_base()])?;
+    Ok(output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// One changed path, from `git status --porcelain=v2 -z`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub path: String,
+    /// Where a rename or copy came from.
+    pub orig_path: Option<String>,
+    /// Change in the index: `.` for none, `A`, `M`, `D`, `R`, `C`, `U`, or
+    /// `?` for untracked files.
+    pub staged: char,
+    /// Change in the working tree, same letters.
+    pub unstaged: char,
+}
+
+impl StatusEntry {
+    /// Whether this change is in the index, i.e. part of the next commit.
+    pub fn is_staged(&self) -> bool {
+        !matches!(self.staged, '.' | '?' | '!')
+    }
+
+    /// Whether there's something in the working tree that `git add` would
+    /// pick up.
+    pub fn is_unstaged(&self) -> bool {
+        self.unstaged != '.' && self.unstaged != '!'
+    }
+
+    /// Whether `HEAD` already has this path, rather than it being new.
+    pub fn is_committed(&self) -> bool {
+        !matches!(self.staged, '?' | '!' | 'A' | 'R' | 'C')
+    }
+
+    /// Whether the path is being removed, in the index or the working tree.
+    pub fn is_deletion(&self) -> bool {
+        self.staged == 'D' || self.unstaged == 'D'
+    }
+}
+
+/// Every changed path in the worktree, including untracked files, limited to
+/// `scope` if given.
+pub fn status(scope: Option<&str>) -> Result<Vec<StatusEntry>, String> {
+    // No optional locks: only look at the index, never rewrite it
+    let mut args = vec![
+        "--no-optional-locks",
+        "status",
+        "--porcelain=v2",
+        "-z",
+        "--untracked-files=all",
+    ];
+    if let Some(scope) = scope {
+        args.extend(["--", scope]);
+    }
+    Ok(parse_status(&git_raw(&args)?))
+}
+
+/// Parse `git status --porcelain=v2 -z`. Paths are NUL-terminated and never
+/// quoted, so spaces, newlines and unicode come through as-is.
+pub fn parse_status(output: &str) -> Vec<StatusEntry> {
+    let mut entries = Vec::new();
+    let mut records = output.split('\0');
+
+    while let Some(record) = records.next() {
+        // Ordinary, rename/copy and unmerged entries have this many fields
+        // before the path, which may itself contain spaces
+        let fields = match record.chars().next() {
+            Some('1') => 8,
+            Some('2') => 9,
+            Some('u') => 10,
+            Some('?') | Some('!') => {
+                let letter = record.chars().next().unwrap();
+                entries.push(StatusEntry {
+                    path: record[2..].to_string(),
+                    orig_path: None,
+                    staged: letter,
+                    unstaged: letter,
+                });
+                continue;
+            }
+            // Headers and the empty tail after the last NUL
+            _ => continue,
+        };
+
+        let mut parts = record.splitn(fields + 1, ' ');
+        let xy: Vec<char> = parts.nth(1).unwrap_or("..").chars().collect();
+        let Some(path) = parts.nth(fields - 2) else {
+            continue;
+        };
+        let orig_path = if record.starts_with('2') {
+            records.next().map(String::from)
+        } else {
+            None
+        };
+
+        entries.push(StatusEntry {
+            path: path.to_string(),
+            orig_path,
+            staged: xy.first().copied().unwrap_or('.'),
+            unstaged: xy.get(1).copied().unwrap_or('.'),
+        });
+    }
+
+    entries
+}
+
+/// Take `paths` out of the index again in a single git call, leaving the
+/// working tree alone.
+///
+/// Equivalent to `git reset HEAD -- <paths>`, but paths are fed to
+/// `git update-index --index-info` as HEAD's entries (or removals for paths
+/// HEAD doesn't have), which stays fast for a whole `node_modules` where
+/// pathspec matching would not.
+pub fn unstage(paths: &[&str]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut wanted: HashSet<&str> = paths.iter().copied().collect();
+    let mut index_info = Vec::new();
+
+    if has_head() {
+        // Same "<mode> <type> <object>\t<path>" records update-index reads
+        let tree = git_raw(&["ls-tree", "-r", "-z", "--full-tree", "HEAD"])?;
+        for record in tree.split('\0') {
+            let Some((_, path)) = record.split_once('\t') else {
+                continue;
+            };
+            if wanted.remove(path) {
+                index_info.extend_from_slice(record.as_bytes());
+                index_info.push(0);
+            }
+        }
+    }
+    // New in the index, mode 0 drops them
+    for path in paths.iter().filter(|path| wanted.contains(*path)) {
+        index_info.extend_from_slice(format!("0 {}\t{}\0", NULL_OID, path).as_bytes());
+    }
+
+    git_with_input(&["update-index", "-z", "--index-info"], index_info).map(drop)
+}
+
+/// Remove `paths` from the index, like `git rm --cached`, keeping the files
+/// on disk. The next commit stops tracking them.
+pub fn untrack(paths: &[&str]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut input = Vec::new();
+    for path in paths {
+        input.extend_from_slice(path.as_bytes());
+        input.push(0);
+    }
+    git_with_input(&["update-index", "-z", "--force-remove", "--stdin"], input).map(drop)
+}
+
+/// Every path in the index.
+pub fn tracked_files() -> Result<Vec<String>, String> {
+    let output = git_raw(&["ls-files", "-z"])?;
+    Ok(output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Paths touched by commits that aren't on any remote yet. Before the first
+/// push that is the whole history.
+pub fn unpushed_files() -> Result<HashSet<String>, String> {
+    if !has_head() {
+        return Ok(HashSet::new());
+    }
+    let output = git_raw(&[
+        "log",
+        "--format=",
+        "--name-only",
+        "-z",
+        "HEAD",
+        "--not",
+        "--remotes",
+    ])?;
+    Ok(output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// At most this many outgoing commits are scanned, newest first.
+pub const MAX_OUTGOING: usize = 100;
+
+/// The commits `git push` would send, newest first and at most
+/// [`MAX_OUTGOING`], each with its patch: what's on top of the push target,
+/// or else of the remote's default branch. Without a remote nothing can be
+/// pushed, so there's nothing to check.
+pub fn outgoing_patches() -> Result<Vec<(String, String)>, String> {
+    let remotes = git(&["remote"])?;
+    let Some(remote) = remotes
+        .lines()
+        .find(|remote| *remote == "origin")
+        .or_else(|| remotes.lines().next())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let max_count = format!("--max-count={}", MAX_OUTGOING);
+    let mut args = vec![
+        "log",
+        "--patch",
+        "--no-color",
+        "--no-ext-diff",
+        "--format=%x00%H",
+        &max_count,
+    ];
+    let default_branch = format!("{}/HEAD", remote);
+    let base = ["@{push}", "@{upstream}", default_branch.as_str()]
+        .into_iter()
+        .find(|base| git(&["rev-parse", "--verify", "--quiet", base]).is_ok());
+    let range = base.map(|base| format!("{}..HEAD", base));
+    match &range {
+        Some(range) => args.push(range),
+        None => args.extend(["HEAD", "--not", "--remotes"]),
+    }
+
+    let output = git_raw(&args)?;
+    Ok(output
+        .split('\0')
+        .filter_map(|commit| {
+            let (sha, patch) = commit.split_once('\n').unwrap_or((commit, ""));
+            (!sha.is_empty()).then(|| (sha.to_string(), patch.to_string()))
+        })
+        .collect())
+}
+
+/// Stage exactly `paths` (including deletions) in a single git call. Paths
+/// are literal, never globs.
+pub fn add(paths: &[&str]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut pathspecs = Vec::new();
+    for path in paths {
+        pathspecs.extend_from_slice(path.as_bytes());
+        pathspecs.push(0);
+    }
+    git_with_input(
+        &[
+            "--literal-pathspecs",
+            "add",
+            "--all",
+            "--pathspec-from-file=-",
+            "--pathspec-file-nul",
+        ],
+        pathspecs,
+    )
+    .map(drop)
+}
+
+/// Stage `contents` as the file `path` without writing it to disk, so the
+/// working tree can be updated once the commit is actually made.
+pub fn stage_contents(path: &str, contents: &str) -> Result<(), String> {
+    let object = git_with_input(
+        &["hash-object", "-w", "--stdin", &format!("--path={}", path)],
+        contents.as_bytes().to_vec(),
+    )?;
+    let index_info = format!("100644 {}\t{}\0", object.trim(), path);
+    git_with_input(
+        &["update-index", "-z", "--add", "--index-info"],
+        index_info.into_bytes(),
+    )
+    .map(drop)
+}
+
+/// Run git with `input` on stdin, for path lists too long for the command
+/// line. Returns what git printed.
+fn git_with_input(args: &[&str], input: Vec<u8>) -> Result<String, String> {
+    let mut child = command()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run git {}: {}", args.join(" "), e))?;
+
+    // Written from another thread so a chatty git can't deadlock us
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run git {}: {}", args.join(" "), e))?;
+    writer
+        .join()
+        .map_err(|_| "failed to send paths to git".to_string())?
+        .map_err(|e| format!("failed to send paths to git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Undoes whatever noob-commit did to the index unless it became a commit.
+///
+/// Clones share the same state, so a signal handler can hold one. Once
+/// restored or released it does nothing, which makes it safe to call from
+/// every exit path.
+#[derive(Clone)]
+pub struct IndexGuard {
+    undo: Arc<Mutex<Option<Undo>>>,
+}
+
+enum Undo {
+    /// Move this copy back over the real index, or delete the index if there
+    /// was none yet.
+    Copy {
+        index: PathBuf,
+        copy: Option<PathBuf>,
+    },
+    /// Throw away this scratch copy, the real index was never touched.
+    Scratch(PathBuf),
+}
+
+impl IndexGuard {
+    /// Copy the index file so it can be put back byte-for-byte, conflicts,
+    /// intent-to-add entries and skip-worktree bits included.
+    pub fn snapshot() -> Result<Self, String> {
+        let index = index_file()?;
+        let copy = if index.exists() {
+            let copy = index.with_extension("noob-commit");
+            std::fs::copy(&index, &copy)
+                .map_err(|e| format!("failed to copy {}: {}", index.display(), e))?;
+            Some(copy)
+        } else {
+            None
+        };
+        Ok(Self::new(Undo::Copy { index, copy }))
+    }
+
+    /// Point every git command this module runs from now on at a scratch
+    /// copy of the index, leaving the real one byte-for-byte alone. Used by
+    /// `--dry-run`.
+    pub fn scratch() -> Result<Self, String> {
+        let index = index_file()?;
+        let scratch =
+            std::env::temp_dir().join(format!("noob-commit-{}.index", std::process::id()));
+
+        // A brand-new repo has no index yet, git will create the scratch one
+        if index.exists() {
+            std::fs::copy(&index, &scratch)
+                .map_err(|e| format!("failed to copy {}: {}", index.display(), e))?;
+        }
+        *SCRATCH_INDEX.lock().unwrap_or_else(|e| e.into_inner()) = Some(scratch.clone());
+
+        Ok(Self::new(Undo::Scratch(scratch)))
+    }
+
+    fn new(undo: Undo) -> Self {
+        Self {
+            undo: Arc::new(Mutex::new(Some(undo))),
+        }
+    }
+
+    fn take(&self) -> Option<Undo> {
+        self.undo.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Put the index back exactly as it was. The working tree is never
+    /// touched. Returns whether the real index had to be restored.
+    pub fn restore(&self) -> Result<bool, String> {
+        match self.take() {
+            // Renamed within the git dir, so it's all or nothing
+            Some(Undo::Copy {
+                index,
+                copy: Some(copy),
+            }) => std::fs::rename(&copy, &index)
+                .map(|_| true)
+                .map_err(|e| format!("failed to move {} back: {}", copy.display(), e)),
+            Some(Undo::Copy { index, copy: None }) => match std::fs::remove_file(&index) {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(format!("failed to remove {}: {}", index.display(), e)),
+            },
+            Some(Undo::Scratch(path)) => {
+                drop_scratch(&path);
+                Ok(false)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The index is now a commit, keep it as it is.
+    pub fn release(&self) {
+        match self.take() {
+            Some(Undo::Copy {
+                copy: Some(copy), ..
+            }) => {
+                std::fs::remove_file(copy).ok();
+            }
+            Some(Undo::Scratch(path)) => drop_scratch(&path),
+            _ => {}
+        }
+    }
+}
+
+/// Absolute path of the real index file.
+fn index_file() -> Result<PathBuf, String> {
+    let index = git_path("index")?;
+    Ok(std::env::current_dir()
+        .map_err(|e| format!("can't find the current directory: {}", e))?
+        .join(index))
+}
+
+/// Go back to the real index and delete the scratch copy.
+fn drop_scratch(path: &Path) {
+    SCRATCH_INDEX
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    std::fs::remove_file(path).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, orig_path: Option<&str>, staged: char, unstaged: char) -> StatusEntry {
+        StatusEntry {
+            path: path.to_string(),
+            orig_path: orig_path.map(String::from),
+            staged,
+            unstaged,
+        }
+    }
+
+    #[test]
+    fn test_parse_status_entry_kinds() {
+        let output = concat!(
+            "# branch.oid abc\0",
+            "1 M. N... 100644 100644 100644 aaa bbb src/main.rs\0",
+            "2 R. N... 100644 100644 100644 aaa aaa R100 new name.rs\0old name.rs\0",
+            "u UU N... 100644 100644 100644 100644 a b c conflict.rs\0",
+            "? node_modules/left pad/index.js\0",
+        );
+
+        assert_eq!(
+            parse_status(output),
+            vec![
+                entry("src/main.rs", None, 'M', '.'),
+                entry("new name.rs", Some("old name.rs"), 'R', '.'),
+                entry("conflict.rs", None, 'U', 'U'),
+                entry("node_modules/left pad/index.js", None, '?', '?'),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_status_odd_filenames() {
+        let output = "1 A. N... 000000 100644 100644 000 bbb line\nbreak \"q\" 🤡.txt\0";
+        let entries = parse_status(output);
+        assert_eq!(entries[0].path, "line\nbreak \"q\" 🤡.txt");
+        assert!(entries[0].is_staged());
+    }
+
+    #[test]
+    fn test_untracked_is_not_staged() {
+        let entries = parse_status(concat!("? build/out.o\0", "1 .M N... 1 1 1 a a lib.rs\0"));
+        assert!(!entries[0].is_staged());
+        assert!(!entries[1].is_staged());
+    }
+
+    #[test]
+    fn test_is_committed() {
+        let entries = parse_status(concat!(
+            "? .env\0",
+            "1 A. N... 0 1 1 a a new.rs\0",
+            "1 .M N... 1 1 1 a a .npmrc\0",
+        ));
+        let committed: Vec<bool> = entries.iter().map(StatusEntry::is_committed).collect();
+        assert_eq!(committed, vec![false, false, true]);
+    }
+}
@@ -0,0 +1,371 @@
+//! Adding entries to `.gitignore`-style files without repeating what's
+//! already there, and starter `.gitignore`s for common stacks.
+//!
+//! The templates in `src/templates/` only hold patterns the built-in filters
+//! would keep out anyway, and lines the repo's own config lets through are
+//! skipped, so ignoring them never hides something noob-commit would have
+//! committed.
+
+use crate::filters::Filters;
+use globset::Glob;
+use std::path::Path;
+
+/// Comment above every block of entries noob-commit adds.
+pub const HEADER: &str = "# Added by noob-commit";
+
+/// The line that ignores `path`, which matched the filter `pattern`: the
+/// pattern itself when gitignore understands it, the one brace alternative
+/// that matched (`*.{pyc,pyo}` becomes `*.pyc`), otherwise the path, anchored
+/// to the top of the repo.
+pub fn entry_for(pattern: &str, path: &str) -> String {
+    // Brace alternatives are a globset extension
+    let (Some(open), Some(close)) = (pattern.find('{'), pattern.rfind('}')) else {
+        return pattern.to_string();
+    };
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let (head, tail) = (&pattern[..open], &pattern[close + 1..]);
+    let single = !pattern.contains('/') && !pattern[open + 1..close].contains(['{', '}']);
+    if single {
+        for alternative in pattern[open + 1..close].split(',') {
+            let entry = format!("{}{}{}", head, alternative, tail);
+            let matches = Glob::new(&entry)
+                .map(|glob| glob.compile_matcher().is_match(file_name))
+                .unwrap_or(false);
+            if matches {
+                return entry;
+            }
+        }
+    }
+    format!("/{}", path)
+}
+
+/// `entries` that `existing` doesn't already have, in order and without
+/// repeats.
+pub fn missing<'a>(existing: &str, entries: &'a [String]) -> Vec<&'a str> {
+    let mut present: Vec<&str> = existing.lines().map(str::trim).collect();
+    let mut missing = Vec::new();
+    for entry in entries {
+        if !present.contains(&entry.as_str()) {
+            present.push(entry);
+            missing.push(entry.as_str());
+        }
+    }
+    missing
+}
+
+/// `existing` followed by `entries` under [`HEADER`]. A block of ours at the
+/// end of the file is added to rather than starting another one.
+pub fn append(existing: &str, entries: &[&str]) -> String {
+    if entries.is_empty() {
+        return existing.to_string();
+    }
+
+    let mut text = existing.to_string();
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let ours_last = existing.rsplit_once(HEADER).is_some_and(|(before, after)| {
+        (before.is_empty() || before.ends_with('\n'))
+            && after.starts_with('\n')
+            && after.lines().skip(1).all(|line| !line.trim().is_empty())
+    });
+    if !ours_last {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(HEADER);
+        text.push('\n');
+    }
+    for entry in entries {
+        text.push_str(entry);
+        text.push('\n');
+    }
+    text
+}
+
+/// Secrets and OS/editor junk, ignored whatever the stack.
+const COMMON: &str = include_str!("templates/common.gitignore");
+
+/// A kind of project, recognized by its marker files.
+#[derive(Debug)]
+pub struct Stack {
+    pub name: &'static str,
+    /// File names, or `*.ext` for any file with that extension.
+    markers: &'static [&'static str],
+    template: &'static str,
+}
+
+/// Every stack `noob-commit init` knows about.
+pub const STACKS: &[Stack] = &[
+    Stack {
+        name: "Rust",
+        markers: &["Cargo.toml"],
+        template: include_str!("templates/rust.gitignore"),
+    },
+    Stack {
+        name: "Node",
+        markers: &["package.json"],
+        template: include_str!("templates/node.gitignore"),
+    },
+    Stack {
+        name: "Python",
+        markers: &["pyproject.toml", "requirements.txt", "setup.py"],
+        template: include_str!("templates/python.gitignore"),
+    },
+    Stack {
+        name: "Go",
+        markers: &["go.mod"],
+        template: include_str!("templates/go.gitignore"),
+    },
+    Stack {
+        name: "Ruby",
+        markers: &["Gemfile"],
+        template: include_str!("templates/ruby.gitignore"),
+    },
+    Stack {
+        name: ".NET",
+        markers: &["*.csproj"],
+        template: include_str!("templates/dotnet.gitignore"),
+    },
+    Stack {
+        name: "iOS",
+        markers: &["Podfile"],
+        template: include_str!("templates/ios.gitignore"),
+    },
+];
+
+impl Stack {
+    fn is_marker(&self, file_name: &str) -> bool {
+        self.markers
+            .iter()
+            .any(|marker| match marker.strip_prefix('*') {
+                Some(extension) => file_name.ends_with(extension),
+                None => file_name == *marker,
+            })
+    }
+}
+
+/// Stacks with a marker file in `root` or one of its direct subfolders
+/// (`frontend/package.json`), in [`STACKS`] order.
+pub fn detect(root: &Path) -> Vec<&'static Stack> {
+    let mut names = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    if let Ok(entries) = std::fs::read_dir(root) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.path().is_dir() && !name.starts_with('.') && name != "node_modules" {
+                dirs.push(entry.path());
+            }
+        }
+    }
+    for dir in dirs {
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            names.extend(
+                entries
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned()),
+            );
+        }
+    }
+
+    STACKS
+        .iter()
+        .filter(|stack| names.iter().any(|name| stack.is_marker(name)))
+        .collect()
+}
+
+/// A path a template line would ignore: `*` becomes a name, a folder gets a
+/// file inside.
+fn example_path(pattern: &str) -> String {
+    let path = pattern
+        .trim_start_matches('/')
+        .replace("**", "a")
+        .replace('*', "x");
+    match path.strip_suffix('/') {
+        Some(dir) => format!("{}/file", dir),
+        None => path,
+    }
+}
+
+/// Whether `entry` is a line of one of the templates. Only those are offered
+/// for `.gitignore`; a `.gitkeep` or a one-off path is better left alone.
+pub fn is_template_line(entry: &str) -> bool {
+    std::iter::once(COMMON)
+        .chain(STACKS.iter().map(|stack| stack.template))
+        .flat_map(|template| template.lines().map(str::trim))
+        .any(|line| !line.starts_with('#') && line == entry)
+}
+
+/// `existing` with whatever the common and `stacks` templates add to it, each
+/// group of patterns under its comment from the template, or `None` if it
+/// has them all. Lines `filters` would let through (say `vendor/` in a repo
+/// that allows it) are left out.
+pub fn starter(existing: &str, stacks: &[&Stack], filters: &Filters) -> Option<String> {
+    let mut known: Vec<&str> = existing.lines().map(str::trim).collect();
+    let mut lines = Vec::new();
+    for template in std::iter::once(COMMON).chain(stacks.iter().map(|stack| stack.template)) {
+        let mut comment = None;
+        for line in template.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if line.starts_with('#') {
+                comment = Some(line);
+            } else if !known.contains(&line)
+                && filters.blocking(&example_path(line), |_| false).is_some()
+            {
+                lines.extend(comment.take());
+                lines.push(line);
+                known.push(line);
+            }
+        }
+    }
+
+    (!lines.is_empty()).then(|| append(existing, &lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{Source, REPO_CONFIG};
+
+    #[test]
+    fn test_entry_for_falls_back_to_the_path() {
+        assert_eq!(
+            entry_for("node_modules/", "web/node_modules/x.js"),
+            "node_modules/"
+        );
+        assert_eq!(entry_for(".env", "api/.env"), ".env");
+        assert_eq!(entry_for("*.{pyc,pyo,pyd}", "tools/gen.pyo"), "*.pyo");
+        assert_eq!(
+            entry_for("*cpython*.{pyc,pyo,pyd}", "a/m.cpython-312.pyc"),
+            "*cpython*.pyc"
+        );
+        assert_eq!(
+            entry_for("**/{build,dist}/**/*.{so,dll}", "build/x/lib.so"),
+            "/build/x/lib.so"
+        );
+    }
+
+    #[test]
+    fn test_append_only_whats_missing() {
+        let existing = "# deps\nnode_modules/\n  .env  ";
+        let entries = vec![
+            ".env".to_string(),
+            "node_modules/".to_string(),
+            "*.log".to_string(),
+            "*.log".to_string(),
+        ];
+
+        let missing = missing(existing, &entries);
+        assert_eq!(missing, vec!["*.log"]);
+        assert_eq!(
+            append(existing, &missing),
+            "# deps\nnode_modules/\n  .env  \n\n# Added by noob-commit\n*.log\n"
+        );
+        assert_eq!(append("", &missing), "# Added by noob-commit\n*.log\n");
+        assert_eq!(append(existing, &[]), existing);
+    }
+
+    #[test]
+    fn test_templates_mirror_the_filters() {
+        let filters = Filters::new(&[]).unwrap();
+        for template in std::iter::once(COMMON).chain(STACKS.iter().map(|s| s.template)) {
+            assert!(template.starts_with("# "), "{}", template);
+            for line in template.lines().filter(|l| !l.starts_with('#')) {
+                let path = example_path(line);
+                assert!(
+                    filters.blocking(&path, |_| false).is_some(),
+                    "'{}' ({}) is ignored but the filters would commit it",
+                    line,
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_only_template_lines_are_offered() {
+        assert!(is_template_line("node_modules/"));
+        assert!(is_template_line("*.pyc"));
+        assert!(!is_template_line(".gitkeep"));
+        assert!(!is_template_line(".keep"));
+        assert!(!is_template_line("/build/x/lib.so"));
+        assert!(!is_template_line("# Node"));
+    }
+
+    #[test]
+    fn test_detect_stacks_at_the_root_and_one_level_down() {
+        let root = std::env::temp_dir().join(format!("noob-commit-detect-{}", std::process::id()));
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("web/node_modules/x")).unwrap();
+        std::fs::create_dir_all(root.join("api/Api")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        std::fs::write(root.join("web/package.json"), "{}").unwrap();
+        std::fs::write(root.join("web/node_modules/x/go.mod"), "").unwrap();
+        std::fs::write(root.join("api/Api.csproj"), "").unwrap();
+        std::fs::write(root.join("api/Api/Gemfile"), "").unwrap();
+
+        let names: Vec<&str> = detect(&root).iter().map(|stack| stack.name).collect();
+        assert_eq!(names, vec!["Rust", "Node", ".NET"]);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_starter_merges_with_an_existing_file() {
+        let stacks: Vec<&Stack> = STACKS
+            .iter()
+            .filter(|stack| stack.name == "Node" || stack.name == "Python")
+            .collect();
+        let existing = "# mine\nnode_modules/\n.env\n*.log\n";
+
+        let filters = Filters::new(&[]).unwrap();
+        let text = starter(existing, &stacks, &filters).unwrap();
+        assert!(text.starts_with(
+            "# mine\nnode_modules/\n.env\n*.log\n\n# Added by noob-commit\n# Secrets\n.env.local\n"
+        ));
+        let lines: Vec<&str> = text.lines().collect();
+        for line in ["node_modules/", ".env", "*.log", "dist/", "__pycache__/"] {
+            assert_eq!(lines.iter().filter(|l| **l == line).count(), 1, "{}", line);
+        }
+        assert!(text.contains("\n# Node\nbower_components/\n"));
+        assert!(text.contains("\n# Python\nvenv/\n"));
+        // coverage.py writes a file, not a folder
+        assert!(text.contains("\nhtmlcov/\n.coverage\n"));
+
+        // Running it again adds nothing
+        assert_eq!(starter(&text, &stacks, &filters), None);
+    }
+
+    #[test]
+    fn test_starter_skips_what_the_config_allows() {
+        let stacks: Vec<&Stack> = STACKS
+            .iter()
+            .filter(|stack| stack.name == "Go" || stack.name == "Ruby")
+            .collect();
+        let config = toml::from_str("[modules]\nallow = [\"vendor/\"]\n").unwrap();
+        let source = Source::File(REPO_CONFIG.into());
+        let filters = Filters::new(&[(source, config)]).unwrap();
+
+        let text = starter("", &stacks, &filters).unwrap();
+        assert!(!text.lines().any(|line| line == "vendor/"), "{}", text);
+        // Both templates are just vendor/, so neither adds a section
+        assert!(!text.contains("# Go"), "{}", text);
+        assert!(!text.contains("# Ruby"), "{}", text);
+        assert!(text.contains("\n# Secrets\n.env\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_append_adds_to_our_last_block() {
+        let ours = "target/\n\n# Added by noob-commit\n.DS_Store\n";
+        assert_eq!(
+            append(ours, &["*.pyc"]),
+            "target/\n\n# Added by noob-commit\n.DS_Store\n*.pyc\n"
+        );
+
+        // Something else came after our block, start a new one
+        let edited = format!("{}\n# mine\ndist/\n", ours);
+        assert_eq!(
+            append(&edited, &["*.pyc"]),
+            format!("{}\n# Added by noob-commit\n*.pyc\n", edited)
+        );
+    }
+}
@@ -1,5 +1,15 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub mod allowlist;
+pub mod budget;
+pub mod filters;
+pub mod git;
+pub mod gitignore;
+pub mod provider;
+pub mod secrets;
+pub mod summarize;
 
 #[derive(Debug, Deserialize, JsonSchema, Serialize)]
 pub struct Commit {
@@ -17,15 +27,15 @@ pub struct CommitAdvice {
     pub commit: Commit,
 }
 
-impl ToString for Commit {
-    fn to_string(&self) -> String {
-        format!("{}\n\n{}", self.title, self.description)
+impl fmt::Display for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.title, self.description)
     }
 }
 
-impl ToString for CommitAdvice {
-    fn to_string(&self) -> String {
-        format!("{}\n\n{}", self.message, self.commit.to_string())
+impl fmt::Display for CommitAdvice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.message, self.commit)
     }
 }
 
@@ -1,30 +1,41 @@
-use async_openai::{
-    config::OpenAIConfig,
-    types::{
-        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
-        ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessage,
-        ChatCompletionRequestUserMessageContent, ChatCompletionTool, ChatCompletionToolType,
-        CreateChatCompletionRequestArgs, FunctionObject,
-    },
-};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
-use log::{error, info};
-use noob_commit::CommitAdvice;
+use log::{error, info, warn};
+use noob_commit::allowlist::{Allowed, Allowlist, ALLOW_FILE, INLINE_MARKER};
+use noob_commit::budget::DiffBudget;
+use noob_commit::filters::{self, Category, Filters};
+use noob_commit::git;
+use noob_commit::gitignore;
+use noob_commit::provider::{
+    parse_headers, AnthropicProvider, CommitGenerator, DiffContext, FallbackChain, FallbackSpec,
+    LlamaCppProvider, OllamaProvider, OpenAiProvider, ProviderKind, RetryPolicy,
+};
+use noob_commit::secrets;
+use noob_commit::summarize::{map_reduce, split_into_chunks, MapReduceMode};
 use question::{Answer, Question};
 use rand::prelude::*;
-use schemars::generate::SchemaSettings;
-use schemars::SchemaGenerator;
 use spinners::{Spinner, Spinners};
 use std::{
+    collections::HashSet,
     env,
     fs::{self, OpenOptions},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
     str,
+    time::Duration,
 };
 
+/// Exit code used when every AI backend failed to write a commit.
+const EXIT_AI_FAILED: i32 = 3;
+
+/// Exit code when the staged changes, or commits about to be pushed, contain
+/// something that looks like a secret.
+const EXIT_SECRET_FOUND: i32 = 4;
+
+/// Exit code after Ctrl-C, as shells report for SIGINT.
+const EXIT_INTERRUPTED: i32 = 130;
+
 #[derive(Parser)]
 #[command(version)]
 #[command(name = "Noob Commit")]
@@ -37,13 +48,39 @@ struct Cli {
     #[arg(
         short = 'd',
         long = "dry-run",
+        global = true,
         help = "🔍 Just show me what commit message you'd create (for anxious devs)"
     )]
     dry_run: bool,
 
+    #[arg(
+        long = "staged-only",
+        env = "NOOB_COMMIT_STAGED_ONLY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new(),
+        help = "🎯 Commit only what I already staged (no 'git add .', for 'git add -p' pros, overrides staged_only in the config)"
+    )]
+    staged_only: Option<bool>,
+
+    #[arg(
+        long,
+        env = "NOOB_COMMIT_PATH",
+        help = "📂 Only auto-add changes under this path (default: the whole repo, wherever you run me)"
+    )]
+    path: Option<String>,
+
+    #[arg(
+        long = "print-prompt",
+        help = "🔎 Print exactly what gets sent to the AI (after secrets are redacted)"
+    )]
+    print_prompt: bool,
+
     #[arg(
         short,
         long,
+        global = true,
         help = "✏️ Let me edit the AI's work (because sometimes AI is also bad at git)"
     )]
     review: bool,
@@ -51,6 +88,7 @@ struct Cli {
     #[arg(
         short,
         long,
+        global = true,
         help = "⚡ YOLO mode - just commit everything (living dangerously)"
     )]
     force: bool,
@@ -58,7 +96,7 @@ struct Cli {
     #[arg(
         short = 'e',
         long = "ok-to-send-env",
-        help = "🔓 Include .env files (for when you want to leak your API keys like a pro)"
+        help = "🔓 Include .env files and commit detected secrets anyway, they're still redacted from the AI (for when you want to leak your API keys like a pro)"
     )]
     ok_to_send_env: bool,
 
@@ -85,13 +123,104 @@ struct Cli {
     )]
     max_input_chars: usize,
 
+    #[arg(
+        short = 'T',
+        long = "max-input-tokens",
+        help = "🪙 Maximum tokens of git diff to send to AI, whole hunks are dropped to fit (0 = unlimited)",
+        default_value = "16000"
+    )]
+    max_input_tokens: usize,
+
+    #[arg(
+        long = "context-tokens",
+        help = "🧠 How many tokens the model can read at once, bigger diffs are summarized in parts (0 = unlimited)",
+        default_value = "128000",
+        env = "NOOB_COMMIT_CONTEXT_TOKENS"
+    )]
+    context_tokens: usize,
+
+    #[arg(
+        long = "map-reduce",
+        help = "🧩 Summarize huge diffs in parts instead of trimming them",
+        value_enum,
+        default_value_t = MapReduceMode::Auto,
+        env = "NOOB_COMMIT_MAP_REDUCE"
+    )]
+    map_reduce: MapReduceMode,
+
     #[arg(
         short = 'm',
         long = "model",
-        help = "🧠 Pick your AI overlord (gpt-4.1-mini is cheap and good enough)",
-        default_value = "gpt-4.1-mini"
+        help = "🧠 Pick your AI overlord (defaults: gpt-4.1-mini for openai, claude-haiku-4-5 for anthropic)",
+        env = "NOOB_COMMIT_MODEL"
+    )]
+    model: Option<String>,
+
+    #[arg(
+        short = 'P',
+        long = "provider",
+        help = "🔌 Which AI backend writes your commits",
+        value_enum,
+        default_value_t = ProviderKind::Openai,
+        env = "NOOB_COMMIT_PROVIDER"
+    )]
+    provider: ProviderKind,
+
+    #[arg(
+        long = "api-base",
+        help = "🏠 Where your AI lives (OpenRouter, vLLM, LM Studio, Azure, http://localhost:11434 for ollama...)",
+        env = "NOOB_COMMIT_API_BASE"
+    )]
+    api_base: Option<String>,
+
+    #[arg(
+        short = 'H',
+        long = "header",
+        help = "📨 Extra 'Name: value' header for every AI request (repeatable)",
+        env = "NOOB_COMMIT_HEADERS",
+        value_delimiter = ';'
+    )]
+    headers: Vec<String>,
+
+    #[arg(
+        short = 'F',
+        long = "fallback",
+        help = "🪂 Backup AIs tried in order when the main one fails, as provider[:model] (e.g. ollama:llama3.2)",
+        env = "NOOB_COMMIT_FALLBACK",
+        value_delimiter = ','
+    )]
+    fallback: Vec<FallbackSpec>,
+
+    #[arg(
+        long = "timeout",
+        help = "⏱️ Seconds to wait for the AI before giving up on an attempt",
+        env = "NOOB_COMMIT_TIMEOUT",
+        default_value = "60"
+    )]
+    timeout: u64,
+
+    #[arg(
+        long = "retries",
+        help = "🔁 Extra attempts per AI on rate limits, server errors and timeouts",
+        env = "NOOB_COMMIT_RETRIES",
+        default_value = "2"
     )]
-    model: String,
+    retries: u32,
+
+    #[arg(
+        long = "azure-deployment",
+        help = "🏢 Azure OpenAI deployment name (defaults to --model)",
+        env = "NOOB_COMMIT_AZURE_DEPLOYMENT"
+    )]
+    azure_deployment: Option<String>,
+
+    #[arg(
+        long = "azure-api-version",
+        help = "🏢 Azure OpenAI api-version",
+        env = "NOOB_COMMIT_AZURE_API_VERSION",
+        default_value = "2024-10-21"
+    )]
+    azure_api_version: String,
 
     #[arg(
         short = 's',
@@ -107,6 +236,13 @@ struct Cli {
     )]
     yes_to_modules: bool,
 
+    #[arg(
+        long = "local-ignore",
+        env = "NOOB_COMMIT_LOCAL_IGNORE",
+        help = "🙈 Put the patterns I offer to ignore in .git/info/exclude instead of .gitignore (just for you, never committed)"
+    )]
+    local_ignore: bool,
+
     #[arg(
         short = 'c',
         long = "yes-to-crap",
@@ -135,6 +271,46 @@ struct Cli {
         help = "🚀 Update noob-commit to the latest version"
     )]
     update: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// 🔎 Look at the rules that keep files out of your commits
+    Filters {
+        #[command(subcommand)]
+        action: FiltersAction,
+    },
+    /// 🐣 Write a starter .gitignore for the stacks in this project (merged with yours)
+    Init,
+    /// 🩺 Find and fix things that shouldn't be in the repo in the first place
+    Doctor {
+        #[command(subcommand)]
+        action: DoctorAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum DoctorAction {
+    /// 🔒 Stop tracking committed secrets and dependency folders, in one commit
+    Secrets,
+}
+
+#[derive(Subcommand)]
+enum FiltersAction {
+    /// 🕵️ Show which rule blocks a path, where it came from and how to allow it
+    Explain {
+        #[arg(
+            help = "📄 Path to check, relative to where you are",
+            required_unless_present = "all"
+        )]
+        path: Option<String>,
+
+        #[arg(long, help = "📋 List every changed path that is currently filtered")]
+        all: bool,
+    },
 }
 
 fn setup_alias() -> Result<(), Box<dyn std::error::Error>> {
@@ -193,20 +369,41 @@ fn setup_alias() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn load_api_key() -> Result<String, String> {
+/// `.env` candidates, nearest first: the current directory and each parent up
+/// to the repository root (just the current directory outside a repo).
+fn env_files() -> Vec<PathBuf> {
+    let Ok(cwd) = env::current_dir().and_then(fs::canonicalize) else {
+        return vec![PathBuf::from(".env")];
+    };
+    let root = git::toplevel().ok();
+
+    let mut files = Vec::new();
+    for dir in cwd.ancestors() {
+        files.push(dir.join(".env"));
+        if root.as_deref().is_none_or(|root| dir == root) {
+            break;
+        }
+    }
+    files
+}
+
+fn load_api_key(var: &str, url: &str) -> Result<String, String> {
     // First, check environment variable
-    if let Ok(key) = env::var("OPENAI_API_KEY") {
+    if let Ok(key) = env::var(var) {
         if !key.is_empty() {
             return Ok(key);
         }
     }
 
-    // If not in environment, try to load from .env file
-    if let Ok(env_content) = fs::read_to_string(".env") {
+    // If not in environment, try the .env files from here up to the repo root
+    for env_file in env_files() {
+        let Ok(env_content) = fs::read_to_string(&env_file) else {
+            continue;
+        };
         for line in env_content.lines() {
             let line = line.trim();
-            if line.starts_with("OPENAI_API_KEY=") {
-                let key = line[15..].trim().trim_matches('"').trim_matches('\'');
+            if let Some(key) = line.strip_prefix(var).and_then(|l| l.strip_prefix('=')) {
+                let key = key.trim().trim_matches('"').trim_matches('\'');
                 if !key.is_empty() {
                     return Ok(key.to_string());
                 }
@@ -214,192 +411,622 @@ fn load_api_key() -> Result<String, String> {
         }
     }
 
-    Err("🔑 Oops! You forgot to set OPENAI_API_KEY. Even noobs need API keys!\n💡 Get one at https://platform.openai.com/api-keys".to_string())
+    Err(format!(
+        "🔑 Oops! You forgot to set {}. Even noobs need API keys!\n💡 Get one at {}",
+        var, url
+    ))
 }
 
-fn is_security_file(filename: &str) -> bool {
-    // Only block exact security file names
-    matches!(
-        filename,
-        ".env"
-            | ".env.local"
-            | ".env.production"
-            | ".env.development"
-            | ".env.test"
-            | ".env.staging"
-            | ".npmrc"
-            | ".pypirc"
-            | "credentials"
-            | "secrets.yml"
-            | "secrets.yaml"
-            | "id_rsa"
-            | "id_ed25519"
-            | "id_ecdsa"
-            | "id_dsa"
-    ) || filename.starts_with(".env.") && filename.ends_with(".local")
+/// Move to the top of the repo, returning where we were relative to it
+/// (e.g. `src/`). Exits with a friendly error outside a repo.
+fn enter_repo_root() -> String {
+    let prefix = git::prefix().unwrap_or_default();
+    match git::toplevel() {
+        Ok(root) => {
+            if let Err(e) = env::set_current_dir(&root) {
+                error!(
+                    "😬 Couldn't move to the repo root {}: {}",
+                    root.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        }
+        Err(_) => {
+            error!("🙈 This isn't a git repo! Run 'git init' first, or cd into your project folder.\n💡 Even noobs need to be in the right directory!");
+            std::process::exit(1);
+        }
+    }
+    prefix
 }
 
-fn is_module_directory(path: &str) -> bool {
-    // Check if any part of the path contains common module/dependency directories
-    let parts: Vec<&str> = path.split('/').collect();
-    for part in parts {
-        if matches!(
-            part,
-            "node_modules" |
-            "venv" |
-            ".venv" |
-            "env" |
-            "virtualenv" |
-            ".virtualenv" |
-            "vendor" |
-            "bower_components" |
-            "jspm_packages" |
-            ".npm" |
-            ".yarn" |
-            ".pnpm-store" |
-            "pip-wheel-metadata" |
-            ".tox" |
-            ".nox" |
-            ".hypothesis" |
-            ".pytest_cache" |
-            "htmlcov" |
-            ".coverage" |
-            "target" |  // Rust
-            "Pods" |    // iOS
-            ".gradle" | // Android
-            "build" |   // Various build systems
-            "dist" // Distribution folders
-        ) {
-            return true;
-        }
-    }
-    false
+/// `path` as seen from the repo root, given where we were (`prefix`).
+fn repo_relative(prefix: &str, path: &str) -> Option<String> {
+    let joined = if Path::new(path).is_absolute() {
+        let root = env::current_dir().ok()?;
+        Path::new(path)
+            .strip_prefix(root)
+            .ok()?
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        format!("{}{}", prefix, path)
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
 }
 
-fn is_crap_file(path: &str) -> bool {
-    // Check if the path contains cache/build artifacts
-    let filename = Path::new(path)
-        .file_name()
-        .and_then(|f| f.to_str())
-        .unwrap_or("");
-
-    // Check exact filenames
-    if matches!(
-        filename,
-        ".DS_Store" | "Thumbs.db" | "desktop.ini" | ".gitkeep" | ".keep"
-    ) {
-        return true;
-    }
-
-    // Check if it's an editor temp file
-    if filename.ends_with(".swp") || filename.ends_with(".swo") || filename.ends_with(".swn") {
-        return true;
-    }
-
-    // Check if it's a backup/temp file
-    if filename.ends_with(".log")
-        || filename.ends_with(".tmp")
-        || filename.ends_with(".temp")
-        || filename.ends_with(".cache")
-        || filename.ends_with(".bak")
-        || filename.ends_with(".backup")
-        || filename.ends_with(".old")
-        || filename.ends_with(".orig")
-        || filename.ends_with("~")
-    {
-        return true;
-    }
-
-    // Check file extensions more carefully
-    // We want to filter actual compiled/generated files, not files that happen to contain
-    // these strings in their name (e.g., "demo.pyc" as a regular file name)
-    if let Some(ext) = Path::new(filename).extension().and_then(|e| e.to_str()) {
-        // Only filter if it's ACTUALLY a compiled/generated file extension
-        match ext {
-            // Python compiled
-            "pyc" | "pyo" | "pyd" => {
-                // Don't filter if it's clearly not a Python bytecode file
-                // Real Python bytecode files are binary and typically in __pycache__
-                // or have names like script.cpython-39.pyc
-                if path.contains("__pycache__") || filename.contains("cpython") {
-                    return true;
-                }
-                // Also filter if it's in a directory that suggests it's compiled
-                if path.contains("/build/")
-                    || path.contains("/dist/")
-                    || path.contains("/.eggs/")
-                    || path.contains("/wheelhouse/")
-                {
-                    return true;
-                }
-                // For standalone .pyc files, we'll be conservative and filter them
-                // unless they're in a source directory (which would be unusual)
-                if !path.contains("/src/") && !path.contains("/docs/") {
-                    return true;
-                }
-                return false;
+/// `noob-commit filters explain`: why a path is (or isn't) kept out.
+fn explain_filters(path: Option<&str>, all: bool) {
+    let prefix = enter_repo_root();
+    let filters = load_filters(Path::new("."));
+    let allowlist = load_allowlist();
+
+    if all {
+        let changed = match git::status(None) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("😬 Couldn't read the changes: {}", e);
+                std::process::exit(1);
             }
-            // Native libraries - be careful not to filter legitimate shared libraries
-            "so" | "dylib" | "dll" => {
-                // Filter if it looks like a build artifact
-                return path.contains("/build/")
-                    || path.contains("/dist/")
-                    || path.contains("/target/")
-                    || path.contains("/.libs/");
+        };
+        let mut filtered = 0;
+        for entry in changed.iter().filter(|entry| !entry.is_deletion()) {
+            let rule = filters.blocking(&entry.path, |_| false);
+            if let (Some(rule), None) = (rule, allowlist.path(&entry.path)) {
+                filtered += 1;
+                println!(
+                    "🚫 {}  ({}: '{}' from {})",
+                    entry.path, rule.category, rule.pattern, rule.source
+                );
             }
-            // Java
-            "class" => return true, // Java compiled files
-            "jar" => {
-                // JAR files in dependencies folders should be filtered
-                return path.contains("/lib/")
-                    || path.contains("/libs/")
-                    || path.contains("/vendor/")
-                    || path.contains("/dependencies/");
+        }
+        if filtered == 0 {
+            println!("✅ Nothing is filtered right now, every change would be committed.");
+        }
+        return;
+    }
+
+    let Some(path) = path.and_then(|path| repo_relative(&prefix, path)) else {
+        error!("🤷 That path isn't inside this repo.");
+        std::process::exit(1);
+    };
+
+    println!("🔎 {}", path);
+    let rules = filters.explain(&path);
+    for rule in &rules {
+        if rule.allow {
+            println!(
+                "   ✅ not a {}: allowed by '{}' from {}",
+                rule.category, rule.pattern, rule.source
+            );
+        } else {
+            println!(
+                "   🚫 {}: matched '{}' from {}",
+                rule.category, rule.pattern, rule.source
+            );
+            println!(
+                "   💡 Include it with {}, or add \"{}\" to allow in the [{}] table of {}",
+                rule.category.flag(),
+                path,
+                rule.category.key(),
+                filters::REPO_CONFIG
+            );
+        }
+    }
+    let blocked = rules.iter().any(|rule| !rule.allow);
+    match allowlist.path(&path) {
+        Some(entry) if blocked => println!(
+            "   ✅ Let through anyway by {}, noob-commit will commit it.",
+            Allowed::Entry(entry)
+        ),
+        _ if !blocked => println!("   ✅ Not filtered, noob-commit will happily commit it."),
+        _ => {}
+    }
+}
+
+/// `noob-commit init`: a `.gitignore` for the stacks we can spot, added to
+/// whatever is there already. Works before `git init` too.
+fn init_gitignore(cli: &Cli) {
+    let root = git::toplevel().unwrap_or_else(|_| PathBuf::from("."));
+    let stacks = gitignore::detect(&root);
+    if stacks.is_empty() {
+        info!(
+            "🤷 Couldn't tell what kind of project this is, adding just the secrets and OS junk."
+        );
+    } else {
+        let names: Vec<&str> = stacks.iter().map(|stack| stack.name).collect();
+        info!("🔍 Looks like {}", names.join(" + "));
+    }
+
+    let path = root.join(".gitignore");
+    let existing = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            error!("😬 Couldn't read {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let filters = load_filters(&root);
+    let Some(text) = gitignore::starter(&existing, &stacks, &filters) else {
+        info!("✨ Your .gitignore already has everything I'd add.");
+        return;
+    };
+
+    let added = &text[existing.len()..];
+    if cli.dry_run {
+        info!("🔍 Would add to {}:\n{}", path.display(), added.trim());
+        return;
+    }
+    if let Err(e) = fs::write(&path, &text) {
+        error!("😬 Couldn't write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    info!(
+        "📝 {} {}:\n{}",
+        if existing.is_empty() {
+            "Wrote"
+        } else {
+            "Added to"
+        },
+        path.display(),
+        added.trim()
+    );
+}
+
+/// The repo's allowlist, or a friendly exit.
+fn load_allowlist() -> Allowlist {
+    match Allowlist::load(Path::new(".")) {
+        Ok(allowlist) => allowlist,
+        Err(e) => {
+            error!(
+                "🤔 Your allowlist doesn't make sense: {}\n💡 One path glob or fingerprint:<id> per line in {}",
+                e, ALLOW_FILE
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The filter rules for the repo we're in, or a friendly exit.
+fn load_filters(root: &Path) -> Filters {
+    match Filters::load(root) {
+        Ok(filters) => filters,
+        Err(e) => {
+            error!(
+                "🤔 Your filter config doesn't make sense: {}\n💡 Check the allow/deny lists in {}",
+                e,
+                filters::REPO_CONFIG
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `noob-commit doctor secrets`: secrets and dependency folders the filters
+/// would never have staged but that got committed anyway are untracked,
+/// ignored and committed in one go. Nothing here is sent to an AI.
+fn doctor_secrets(cli: &Cli) {
+    enter_repo_root();
+    let filters = load_filters(Path::new("."));
+    let tracked = match git::tracked_files() {
+        Ok(tracked) => tracked,
+        Err(e) => {
+            error!("😬 Couldn't list the tracked files: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Committed junk is harmless, and some of it (.gitkeep) is there on purpose
+    let allowed = |category| match category {
+        Category::Security => cli.ok_to_send_env,
+        Category::Modules => cli.yes_to_modules,
+        Category::Crap => true,
+    };
+    let allowlist = load_allowlist();
+    let found: Vec<(&str, &filters::Rule)> = tracked
+        .iter()
+        .filter(|path| allowlist.path(path).is_none())
+        .filter_map(|path| {
+            filters
+                .blocking(path, allowed)
+                .map(|rule| (path.as_str(), rule))
+        })
+        .collect();
+    if found.is_empty() {
+        info!("✨ Nothing to clean up, no tracked secrets or dependency folders.");
+        return;
+    }
+
+    // One line per rule, a whole node_modules is one problem, not 40k
+    let mut by_rule: Vec<(&filters::Rule, Vec<&str>)> = Vec::new();
+    for (path, rule) in &found {
+        match by_rule.iter_mut().find(|(r, _)| std::ptr::eq(*r, *rule)) {
+            Some((_, paths)) => paths.push(path),
+            None => by_rule.push((rule, vec![path])),
+        }
+    }
+    let unpushed = git::unpushed_files().unwrap_or_default();
+    let mut entries = Vec::new();
+    for (rule, paths) in &by_rule {
+        let examples = paths.iter().take(3).copied().collect::<Vec<_>>().join(", ");
+        let more = match paths.len() {
+            n if n > 3 => format!(" and {} more", n - 3),
+            _ => String::new(),
+        };
+        warn!(
+            "🚨 Tracked {} ('{}'): {}{}",
+            rule.category, rule.pattern, examples, more
+        );
+        let in_unpushed = paths.iter().filter(|p| unpushed.contains(**p)).count();
+        if in_unpushed > 0 {
+            warn!("   ⚠️  {} of them are in commits you haven't pushed yet, untracking won't take them out of those.\n   💡 Rewrite those commits before pushing (git rebase -i), or they'll go up with the next push.", in_unpushed);
+        }
+        for path in paths {
+            entries.push(gitignore::entry_for(&rule.pattern, path));
+        }
+    }
+    if found
+        .iter()
+        .any(|(path, rule)| rule.category == Category::Security && !unpushed.contains(*path))
+    {
+        warn!("🔑 Secrets that were already pushed are out there: rotate them, untracking doesn't unleak them.");
+    }
+
+    let gitignore_path = Path::new(".gitignore");
+    let original = fs::read_to_string(gitignore_path).unwrap_or_default();
+    let new_entries = gitignore::missing(&original, &entries);
+    info!(
+        "🩺 Plan: stop tracking {} file(s) (they stay on disk) and add {} to .gitignore, in one commit.",
+        found.len(),
+        if new_entries.is_empty() {
+            "nothing".to_string()
+        } else {
+            new_entries.join(" ")
+        }
+    );
+    if cli.dry_run {
+        info!("🔍 Dry run, nothing changed.");
+        return;
+    }
+
+    // The cleanup gets a commit of its own
+    if !git::staged_files().unwrap_or_default().is_empty() {
+        error!("✋ You have staged changes, they'd end up in the cleanup commit.\n💡 Commit or unstage them first, then run me again.");
+        std::process::exit(1);
+    }
+    if !cli.force {
+        let answer = Question::new("Do you want to continue? (Y/n)")
+            .yes_no()
+            .until_acceptable()
+            .default(Answer::YES)
+            .ask()
+            .expect("Couldn't ask question.");
+        if answer == Answer::NO {
+            info!("👍 Left everything as it was.");
+            return;
+        }
+    }
+
+    let guard = match git::IndexGuard::snapshot() {
+        Ok(guard) => guard,
+        Err(e) => {
+            error!("😬 Couldn't back up the index, not touching it: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let fail = |msg: String| -> ! {
+        error!("{}", msg);
+        if fs::read_to_string(gitignore_path).unwrap_or_default() != original {
+            let _ = fs::write(gitignore_path, &original);
+        }
+        restore_and_exit(&guard, 1);
+    };
+
+    let paths: Vec<&str> = found.iter().map(|(path, _)| *path).collect();
+    if let Err(e) = git::untrack(&paths) {
+        fail(format!(
+            "😬 Failed to untrack {} file(s): {}",
+            paths.len(),
+            e
+        ));
+    }
+    if !new_entries.is_empty() {
+        if let Err(e) = fs::write(gitignore_path, gitignore::append(&original, &new_entries)) {
+            fail(format!("😬 Couldn't update .gitignore: {}", e));
+        }
+        if let Err(e) = git::add(&[".gitignore"]) {
+            fail(format!("😬 Couldn't stage .gitignore: {}", e));
+        }
+    }
+
+    let mut message = String::from("Stop tracking files that should be ignored\n\n");
+    for (rule, paths) in &by_rule {
+        message.push_str(&format!(
+            "- {} {} matching '{}'\n",
+            paths.len(),
+            rule.category,
+            rule.pattern
+        ));
+    }
+    let commit = Command::new("git")
+        .arg("commit")
+        .args(if cli.review { vec!["-e"] } else { vec![] })
+        .args(["-q", "-m", &message])
+        .status();
+    if !matches!(commit, Ok(status) if status.success()) {
+        fail(
+            "😬 git commit failed!\n💡 Check the output above, fix it and run me again."
+                .to_string(),
+        );
+    }
+    guard.release();
+    info!(
+        "✅ Untracked {} file(s), they're still on your disk and ignored from now on.",
+        found.len()
+    );
+}
+
+/// Patterns to add to `.gitignore` or `.git/info/exclude`, written only once
+/// the commit is made so an abort or a failed AI call leaves the file alone.
+struct IgnoreEdit {
+    path: PathBuf,
+    text: String,
+    added: usize,
+}
+
+impl IgnoreEdit {
+    fn write(&self) {
+        let written = self
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, &self.text));
+        match written {
+            Ok(()) => info!(
+                "📝 Added {} pattern(s) to {}",
+                self.added,
+                self.path.display()
+            ),
+            Err(e) => warn!("⚠️  Couldn't update {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Offer to ignore the dependency folders and junk we just left out, so the
+/// next run doesn't rediscover them. A `.gitignore` edit is staged right
+/// away to go into the commit, the file itself is only written afterwards.
+fn offer_to_ignore(cli: &Cli, entries: &[String]) -> Option<IgnoreEdit> {
+    let path = if cli.local_ignore {
+        match git::git_path("info/exclude") {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("⚠️  Couldn't find .git/info/exclude: {}", e);
+                return None;
             }
-            // C/C++ objects
-            "o" | "a" => {
-                // Object files and archives are typically build artifacts
-                return true;
+        }
+    } else {
+        PathBuf::from(".gitignore")
+    };
+    let existing = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            warn!("⚠️  Couldn't read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let missing = gitignore::missing(&existing, entries);
+    if missing.is_empty() {
+        return None;
+    }
+
+    info!(
+        "🙈 Not ignored yet, so you'll see them again next time: {}",
+        missing.join(" ")
+    );
+    if cli.dry_run {
+        info!("💡 I'd offer to add them to {}", path.display());
+        return None;
+    }
+    if !cli.force {
+        let answer = Question::new(&format!("Add them to {}? (Y/n)", path.display()))
+            .yes_no()
+            .until_acceptable()
+            .default(Answer::YES)
+            .ask()
+            .expect("Couldn't ask question.");
+        if answer == Answer::NO {
+            return None;
+        }
+    }
+
+    Some(IgnoreEdit {
+        text: gitignore::append(&existing, &missing),
+        added: missing.len(),
+        path,
+    })
+}
+
+/// Refuse to push commits that contain secrets. The commit we just made is
+/// already done, so this only ever stops the push.
+fn check_outgoing_commits(allowlist: &Allowlist) {
+    let commits = match git::outgoing_patches() {
+        Ok(commits) => commits,
+        Err(e) => {
+            error!(
+                "😬 Couldn't check the commits you're about to push: {}\n💡 Not pushing, run 'git push' yourself once you've had a look.",
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+
+    if commits.len() == git::MAX_OUTGOING {
+        warn!(
+            "⚠️  Only checked the newest {} commits you're pushing, look over the older ones yourself.",
+            git::MAX_OUTGOING
+        );
+    }
+
+    let mut found = false;
+    for (sha, patch) in &commits {
+        let at = format!("{} ", &sha[..sha.len().min(8)]);
+        found |= report_findings(secrets::scan_diff(patch), allowlist, &at);
+    }
+    if found {
+        error!("🛑 Committed, but not pushing: the commits above would send secrets to the remote!\n💡 Take them out with 'git rebase -i', or if they're fake allow them in {} and run 'git push' yourself.", ALLOW_FILE);
+        std::process::exit(EXIT_SECRET_FOUND);
+    }
+}
+
+/// Log every finding, saying which allowlist entry let it through if one
+/// did. `at` goes before the path, e.g. a commit. True if any is not allowed.
+fn report_findings(findings: Vec<secrets::Finding>, allowlist: &Allowlist, at: &str) -> bool {
+    let mut blocking = false;
+    for finding in &findings {
+        match allowlist.finding(finding) {
+            Some(allowed) => info!(
+                "🙈 Allowing {} in {}{}:{} ({})",
+                finding.kind, at, finding.path, finding.line, allowed
+            ),
+            None => {
+                blocking = true;
+                error!(
+                    "🔑 {} in {}{}:{} ({}, fingerprint {})",
+                    finding.kind,
+                    at,
+                    finding.path,
+                    finding.line,
+                    finding.masked(),
+                    finding.fingerprint()
+                );
             }
-            // Windows executables
-            "exe" => {
-                // Filter if in common build directories
-                return path.contains("/build/")
-                    || path.contains("/dist/")
-                    || path.contains("/target/")
-                    || path.contains("/bin/")
-                    || path.contains("/Debug/")
-                    || path.contains("/Release/");
+        }
+    }
+    blocking
+}
+
+/// Put the index back the way the user left it and exit with `code`.
+fn restore_and_exit(guard: &git::IndexGuard, code: i32) -> ! {
+    match guard.restore() {
+        Ok(true) => info!("↩️  Put your staged files back the way they were."),
+        Ok(false) => {}
+        Err(e) => error!("⚠️  Failed to restore the index: {}", e),
+    }
+    std::process::exit(code);
+}
+
+/// Outline of the files in a first commit, one line per top-level entry.
+fn project_layout(files: &[String]) -> String {
+    let mut entries: Vec<(String, usize)> = Vec::new();
+    for file in files {
+        let entry = match file.split_once('/') {
+            Some((dir, _)) => format!("{}/", dir),
+            None => file.clone(),
+        };
+        match entries.iter_mut().find(|(name, _)| *name == entry) {
+            Some((_, count)) => *count += 1,
+            None => entries.push((entry, 1)),
+        }
+    }
+
+    let mut layout = format!("Project layout ({} files):\n", files.len());
+    for (entry, count) in entries {
+        if entry.ends_with('/') {
+            layout.push_str(&format!("- {} ({} files)\n", entry, count));
+        } else {
+            layout.push_str(&format!("- {}\n", entry));
+        }
+    }
+    layout
+}
+
+fn build_generator(
+    cli: &Cli,
+    kind: ProviderKind,
+    model: Option<&str>,
+    api_base: Option<&str>,
+    headers: &[String],
+) -> Result<Box<dyn CommitGenerator>, String> {
+    // Local backends don't need (or want) an API key
+    let api_token = match (kind.api_key_var(), kind.api_key_url()) {
+        (Some(var), Some(url)) => load_api_key(var, url)?,
+        _ => String::new(),
+    };
+    let model = model.unwrap_or(kind.default_model());
+    let api_base = api_base.or(kind.default_api_base());
+    let headers = parse_headers(headers).map_err(|e| format!("📨 Bad --header: {}", e))?;
+
+    Ok(match (kind, api_base) {
+        (ProviderKind::Openai, None) => {
+            Box::new(OpenAiProvider::new(api_token, model).with_headers(headers))
+        }
+        (ProviderKind::Openai, Some(api_base)) => {
+            Box::new(OpenAiProvider::compatible(api_token, api_base, model).with_headers(headers))
+        }
+        (ProviderKind::Azure, None) => {
+            return Err("🏢 Azure mode needs to know where your resource lives!\n💡 Use --api-base https://<resource>.openai.azure.com (or NOOB_COMMIT_API_BASE)".to_string());
+        }
+        (ProviderKind::Azure, Some(api_base)) => {
+            let deployment = cli.azure_deployment.as_deref().unwrap_or(model);
+            Box::new(
+                OpenAiProvider::azure(api_token, api_base, deployment, &cli.azure_api_version)
+                    .with_headers(headers),
+            )
+        }
+        (ProviderKind::Anthropic, api_base) => {
+            let mut provider = AnthropicProvider::new(api_token, model).with_headers(headers);
+            if let Some(api_base) = api_base {
+                provider = provider.with_api_base(api_base);
             }
-            // Debug databases
-            "idb" | "pdb" => return true,
-            // Other build artifacts
-            "sage" => return true,
-            _ => {}
-        }
-    }
-
-    // Special handling for egg-info (it's a directory suffix, not a file extension)
-    if filename.ends_with(".egg-info") || path.contains(".egg-info/") {
-        return true;
-    }
-
-    // Check if path contains cache directories
-    path.contains("/__pycache__/")
-        || path.contains("/.pytest_cache/")
-        || path.contains("/.mypy_cache/")
-        || path.contains("/.ruff_cache/")
-        || path.contains("/.sass-cache/")
-        || path.contains("/.cache/")
-        || path.contains("/.parcel-cache/")
-        || path.contains("/.next/")
-        || path.contains("/.nuxt/")
-        || path.contains("/.docusaurus/")
-        || path.contains("/.serverless/")
-        || path.contains("/.fusebox/")
-        || path.contains("/.dynamodb/")
-        || path.contains("/.tern-port")
-        || path.contains("/.yarn-integrity")
+            Box::new(provider)
+        }
+        (ProviderKind::Ollama, api_base) => {
+            Box::new(OllamaProvider::new(api_base.unwrap_or_default(), model).with_headers(headers))
+        }
+        (ProviderKind::LlamaCpp, api_base) => Box::new(
+            LlamaCppProvider::new(api_base.unwrap_or_default(), model).with_headers(headers),
+        ),
+    })
+}
+
+/// The primary provider followed by every usable `--fallback` entry.
+fn build_chain(cli: &Cli) -> Result<FallbackChain, String> {
+    let mut generators = vec![build_generator(
+        cli,
+        cli.provider,
+        cli.model.as_deref(),
+        cli.api_base.as_deref(),
+        &cli.headers,
+    )?];
+
+    // --api-base and --header belong to the primary provider only
+    for spec in &cli.fallback {
+        match build_generator(cli, spec.kind, spec.model.as_deref(), None, &[]) {
+            Ok(generator) => generators.push(generator),
+            Err(msg) => warn!("⏭️  Skipping fallback {}: {}", spec.kind, msg),
+        }
+    }
+
+    let policy = RetryPolicy {
+        max_retries: cli.retries,
+        timeout: Duration::from_secs(cli.timeout),
+        ..RetryPolicy::default()
+    };
+    Ok(FallbackChain::new(generators, policy).with_prompt_printing(cli.print_prompt))
 }
 
 #[tokio::main]
@@ -425,12 +1052,31 @@ async fn main() -> Result<(), ()> {
         }
     }
 
+    if let Some(Commands::Filters {
+        action: FiltersAction::Explain { path, all },
+    }) = &cli.command
+    {
+        explain_filters(path.as_deref(), *all);
+        return Ok(());
+    }
+    if let Some(Commands::Init) = &cli.command {
+        init_gitignore(&cli);
+        return Ok(());
+    }
+    if let Some(Commands::Doctor {
+        action: DoctorAction::Secrets,
+    }) = &cli.command
+    {
+        doctor_secrets(&cli);
+        return Ok(());
+    }
+
     // Handle update
     if cli.update {
         info!("🚀 Updating noob-commit to the latest version...");
         
         let update_output = Command::new("cargo")
-            .args(&["install", "noob-commit", "--force"])
+            .args(["install", "noob-commit", "--force"])
             .output()
             .expect("Failed to run cargo install");
         
@@ -446,8 +1092,8 @@ async fn main() -> Result<(), ()> {
         }
     }
 
-    let api_token = match load_api_key() {
-        Ok(key) => key,
+    let generator = match build_chain(&cli) {
+        Ok(generator) => generator,
         Err(msg) => {
             error!("{}", msg);
             std::process::exit(1);
@@ -467,129 +1113,325 @@ async fn main() -> Result<(), ()> {
         std::process::exit(1);
     }
 
-    // Auto-add files, but exclude security files unless explicitly allowed
-    let _add_output = Command::new("git")
-        .arg("add")
-        .arg(".")
-        .output()
-        .expect("Failed to add files");
+    // Work from the top of the repo so running from src/ behaves the same as
+    // running from the root. A --path scope is taken relative to where we were.
+    let prefix = enter_repo_root();
+    let scope = cli.path.as_ref().map(|path| {
+        if Path::new(path).is_absolute() {
+            path.clone()
+        } else {
+            format!("{}{}", prefix, path)
+        }
+    });
 
-    // Get list of all files in the repository
-    let all_files_output = Command::new("git")
-        .arg("ls-files")
-        .arg("--cached")
-        .output()
-        .expect("Failed to list git files");
+    let configs = filters::load_configs(Path::new("."));
+    // The flag beats the config files, and the repo's beats the global one
+    let staged_only = cli
+        .staged_only
+        .or_else(|| {
+            configs
+                .iter()
+                .flatten()
+                .rev()
+                .find_map(|(_, config)| config.staged_only)
+        })
+        .unwrap_or(false);
+    let patterns: Vec<String> = configs
+        .iter()
+        .flatten()
+        .flat_map(|(_, config)| config.redact.patterns.iter().cloned())
+        .collect();
+    let loaded = configs
+        .and_then(|configs| Ok((Filters::new(&configs)?, secrets::Redactor::new(&patterns)?)));
+    let (filters, mut redactor) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            error!(
+                "🤔 Your filter config doesn't make sense: {}\n💡 Check the allow/deny lists and redact patterns in {}",
+                e,
+                filters::REPO_CONFIG
+            );
+            std::process::exit(1);
+        }
+    };
+    let allowlist = load_allowlist();
+
+    // A dry run stages into a scratch copy of the index so the real one is
+    // never touched, otherwise remember the index so a failed run doesn't
+    // leave it half-staged
+    let index_guard = if cli.dry_run {
+        match git::IndexGuard::scratch() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("😬 Couldn't set up a safe dry run: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        match git::IndexGuard::snapshot() {
+            Ok(guard) => guard,
+            Err(e) => {
+                error!("😬 Couldn't back up the index, not touching it: {}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // Ctrl-C while we're waiting on the AI or the prompt shouldn't cost the
+    // noob their staged files
+    let guard = index_guard.clone();
+    let handler = ctrlc::set_handler(move || {
+        if let Ok(true) = guard.restore() {
+            error!("🛑 Interrupted! Put your staged files back the way they were.");
+        }
+        std::process::exit(EXIT_INTERRUPTED);
+    });
+    if let Err(e) = handler {
+        warn!(
+            "⚠️  Couldn't catch Ctrl-C, the index won't be restored if you interrupt: {}",
+            e
+        );
+    }
 
-    let all_files = str::from_utf8(&all_files_output.stdout).unwrap();
+    // Decide on every changed path before anything is staged, so filtered
+    // files never reach the index (or .git/objects). With --staged-only the
+    // index is used as-is and only checked. --path only limits what gets
+    // added: whatever is staged already is checked wherever it is.
+    let status = |scope| match git::status(scope) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("😬 Couldn't read the changes: {}", e);
+            restore_and_exit(&index_guard, 1);
+        }
+    };
+    let changed = status(None);
+    let in_scope: Option<HashSet<String>> = match &scope {
+        Some(scope) if !staged_only => Some(
+            status(Some(scope.as_str()))
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect(),
+        ),
+        _ => None,
+    };
+    let in_scope = |path: &str| in_scope.as_ref().is_none_or(|paths| paths.contains(path));
+    let mut to_add = Vec::new();
     let mut unstaged_security = false;
     let mut unstaged_modules = false;
     let mut unstaged_crap = false;
-
-    for file_path in all_files.lines() {
-        let mut should_unstage = false;
-        let mut reason = "";
-
-        // Check security files
-        if !cli.ok_to_send_env {
-            if let Some(filename) = Path::new(file_path).file_name() {
-                if let Some(filename_str) = filename.to_str() {
-                    if is_security_file(filename_str) {
-                        should_unstage = true;
-                        reason = "security file";
-                        unstaged_security = true;
-                    }
-                }
+    let mut blocked = Vec::new();
+    let mut committed_anyway = false;
+    let mut to_unstage = Vec::new();
+    let mut to_ignore = Vec::new();
+
+    for entry in &changed {
+        let file_path = entry.path.as_str();
+        if !entry.is_staged() && (staged_only || !in_scope(file_path)) {
+            continue;
+        }
+        let wants_adding = !staged_only && entry.is_unstaged() && in_scope(file_path);
+        // Removing a committed .env or node_modules is a good thing
+        if entry.is_deletion() {
+            if wants_adding {
+                to_add.push(file_path);
             }
+            continue;
         }
 
-        // Check module directories
-        if !cli.yes_to_modules && is_module_directory(file_path) {
-            should_unstage = true;
-            reason = "dependency/module folder";
-            unstaged_modules = true;
+        let allowed = |category| match category {
+            Category::Security => cli.ok_to_send_env,
+            Category::Modules => cli.yes_to_modules,
+            Category::Crap => cli.yes_to_crap,
+        };
+        let mut rule = filters.blocking(file_path, allowed);
+        if let (Some(_), Some(entry)) = (rule, allowlist.path(file_path)) {
+            info!(
+                "✅ Including {} anyway ({})",
+                file_path,
+                Allowed::Entry(entry)
+            );
+            rule = None;
         }
-
-        // Check crap files
-        if !cli.yes_to_crap && is_crap_file(file_path) {
-            should_unstage = true;
-            reason = "cache/build artifact";
-            unstaged_crap = true;
+        let Some(rule) = rule else {
+            if wants_adding {
+                to_add.push(file_path);
+            }
+            continue;
+        };
+        match rule.category {
+            Category::Security => unstaged_security = true,
+            Category::Modules => unstaged_modules = true,
+            Category::Crap => unstaged_crap = true,
+        }
+        // Leaving out its new changes doesn't take it out of the repo
+        if entry.is_committed() {
+            committed_anyway = true;
+            if rule.category == Category::Security {
+                warn!(
+                    "🚨 {} is already committed, its secrets are in your history!",
+                    file_path
+                );
+            }
         }
 
-        if should_unstage {
+        if staged_only {
+            // Never touch a hand-crafted index: secrets stop the run, the
+            // rest is only pointed out
+            if rule.category == Category::Security {
+                error!("🔒 You staged a security file: {}", file_path);
+                blocked.push(file_path);
+            } else {
+                warn!(
+                    "⚠️  You staged a {} (use {} to silence this): {}",
+                    rule.category,
+                    rule.category.flag(),
+                    file_path
+                );
+            }
+        } else {
             info!(
-                "🛡️  Protecting {} ({}): {}",
-                reason,
-                if reason == "security file" {
-                    "use --ok-to-send-env to include"
-                } else if reason == "dependency/module folder" {
-                    "use --yes-to-modules to include"
-                } else {
-                    "use --yes-to-crap to include"
-                },
+                "🛡️  Protecting {} (use {} to include): {}",
+                rule.category,
+                rule.category.flag(),
                 file_path
             );
 
-            let unstage_result = Command::new("git")
-                .arg("reset")
-                .arg("HEAD")
-                .arg(file_path)
-                .output();
-
-            if let Err(e) = unstage_result {
-                error!("⚠️  Failed to unstage {}: {}", file_path, e);
+            // Only already staged files need taking out, the rest is
+            // simply never added
+            if entry.is_staged() {
+                to_unstage.push(file_path);
+                // A rename also deleted its source, put that back too
+                if entry.staged == 'R' {
+                    to_unstage.extend(entry.orig_path.as_deref());
+                }
+            }
+            if rule.category != Category::Security && !entry.is_committed() {
+                let pattern = gitignore::entry_for(&rule.pattern, file_path);
+                if gitignore::is_template_line(&pattern) {
+                    to_ignore.push(pattern);
+                }
             }
         }
     }
 
+    let ignore_edit = if to_ignore.is_empty() {
+        None
+    } else {
+        offer_to_ignore(&cli, &to_ignore)
+    };
+
+    // One git call for all of them, even for a whole node_modules
+    if let Err(e) = git::unstage(&to_unstage) {
+        error!("⚠️  Failed to unstage {} file(s): {}", to_unstage.len(), e);
+        restore_and_exit(&index_guard, 1);
+    }
+
+    if let Err(e) = git::add(&to_add) {
+        error!("😬 Failed to add {} file(s): {}", to_add.len(), e);
+        restore_and_exit(&index_guard, 1);
+    }
+
+    if let Some(edit) = ignore_edit.as_ref().filter(|_| !cli.local_ignore) {
+        if let Err(e) = git::stage_contents(".gitignore", &edit.text) {
+            error!("😬 Failed to stage .gitignore: {}", e);
+            restore_and_exit(&index_guard, 1);
+        }
+    }
+
+    if !blocked.is_empty() {
+        error!("🛑 Not sending your secrets anywhere! Unstage them first:\n   git restore --staged -- {}\n💡 Use --ok-to-send-env if you really want to include them (not recommended)", blocked.join(" "));
+        restore_and_exit(&index_guard, 1);
+    }
+
     // Show summary messages
-    if unstaged_security {
-        info!("🔒 Unstaged security files to protect your secrets!");
+    if unstaged_security && !staged_only {
+        info!("🔒 Left security files out to protect your secrets!");
         info!("💡 Use --ok-to-send-env if you really want to include them (not recommended)");
     }
 
-    if unstaged_modules {
-        info!("📦 Unstaged dependency folders to keep your repo size reasonable!");
+    if unstaged_modules && !staged_only {
+        info!("📦 Left dependency folders out to keep your repo size reasonable!");
         info!("💡 Use --yes-to-modules if you really want to include them (repo will be HUGE!)");
     }
 
-    if unstaged_crap {
-        info!("🗑️  Unstaged cache/build artifacts to keep your repo clean!");
+    if unstaged_crap && !staged_only {
+        info!("🗑️  Left cache/build artifacts out to keep your repo clean!");
         info!("💡 Use --yes-to-crap if you really want to include them (not recommended)");
     }
 
-    let git_staged_cmd = Command::new("git")
-        .arg("diff")
-        .arg("--staged")
-        .output()
-        .expect("Couldn't find diff.")
-        .stdout;
+    if committed_anyway {
+        info!("💡 Some of these are committed already, 'noob-commit doctor secrets' can stop tracking them.");
+    }
 
-    let git_staged_cmd = str::from_utf8(&git_staged_cmd).unwrap();
+    // Exactly what will be committed: the index against HEAD, never the
+    // working tree (which still has the changes we left out)
+    let output = match git::staged_diff() {
+        Ok(diff) => diff,
+        Err(e) => {
+            error!("😬 Couldn't read the staged changes: {}", e);
+            restore_and_exit(&index_guard, 1);
+        }
+    };
 
-    if git_staged_cmd.is_empty() {
+    if output.is_empty() && staged_only {
+        error!("🤷 Nothing staged! You asked me to use only what's staged.\n💡 Stage something first with 'git add -p', or drop --staged-only.");
+        restore_and_exit(&index_guard, 1);
+    }
+    if output.is_empty() {
         error!("🤷 Nothing to commit! Did you actually write any code?\n💡 If you did, something went wrong with auto-adding files.");
-        std::process::exit(1);
+        restore_and_exit(&index_guard, 1);
     }
 
-    let client = async_openai::Client::with_config(OpenAIConfig::new().with_api_key(api_token));
+    // A key pasted into code has no telltale file name, so look inside
+    if !cli.ok_to_send_env && report_findings(secrets::scan_diff(&output), &allowlist, "") {
+        error!("🛑 Not committing that, and not sending it to any AI either! Remove the secret(s) and run me again.\n💡 Fake ones (test fixtures...)? Add a '{}' comment on the line, or the path or fingerprint to {}.", INLINE_MARKER, ALLOW_FILE);
+        restore_and_exit(&index_guard, EXIT_SECRET_FOUND);
+    }
 
-    let output = Command::new("git")
-        .arg("diff")
-        .arg("HEAD")
-        .output()
-        .expect("Couldn't find diff.")
-        .stdout;
-    let mut output = str::from_utf8(&output).unwrap().to_string();
-    
-    // Trim the git diff if it exceeds max_input_chars
-    if cli.max_input_chars > 0 && output.len() > cli.max_input_chars {
-        info!("✂️  Trimming git diff from {} to {} characters", output.len(), cli.max_input_chars);
-        output.truncate(cli.max_input_chars);
-        output.push_str("\n... (diff truncated due to size limit)");
+    // Whatever gets committed, key-shaped strings never leave the machine
+    let output = redactor.redact_diff(&output);
+    if redactor.redacted() > 0 {
+        info!(
+            "🙈 Redacted {} secret(s) from what the AI gets to see",
+            redactor.redacted()
+        );
     }
 
+    let budget = DiffBudget::new(cli.max_input_tokens, cli.max_input_chars);
+    let use_map_reduce = match cli.map_reduce {
+        MapReduceMode::Always => true,
+        MapReduceMode::Never => false,
+        MapReduceMode::Auto => !DiffBudget::new(cli.context_tokens, 0).fits_text(&output),
+    };
+    let chunks = if use_map_reduce {
+        split_into_chunks(&output, &budget)
+    } else {
+        Vec::new()
+    };
+    // Diffs too big for the model are split for map-reduce, everything else
+    // (including a diff that fits in one chunk anyway) is fit into the budget
+    // without splitting characters or hunks
+    let use_map_reduce = chunks.len() > 1;
+    let output = if use_map_reduce {
+        String::new()
+    } else {
+        let budgeted = budget.apply(&output);
+        if budgeted.is_trimmed() {
+            info!(
+                "✂️  Trimming git diff to {} tokens, left out parts of {} file(s):",
+                budgeted.tokens,
+                budgeted.omitted.len()
+            );
+            for omission in &budgeted.omitted {
+                info!(
+                    "   - {} ({} of {} hunks)",
+                    omission.path, omission.hunks_dropped, omission.hunks_total
+                );
+            }
+        }
+        budgeted.text
+    };
+
     if !cli.dry_run {
         info!("Loading Data...");
     }
@@ -630,83 +1472,48 @@ async fn main() -> Result<(), ()> {
         None
     };
 
-    let settings = SchemaSettings::openapi3().with(|s| {
-        s.inline_subschemas = true;
-    });
-    let mut generator = SchemaGenerator::new(settings);
-
-    let commit_schema = generator.subschema_for::<CommitAdvice>();
-
     let mut system_prompt = "You are an experienced programmer who writes great commit messages. Analyze the git diff and return JSON with a 'message' for the noob developer and a 'commit' containing title and description. If you find any API keys, mention 'WARNING!!! API_KEY DETECTED IN THIS PART' in the message.".to_string();
     if !cli.no_f_ads {
         system_prompt.push_str(" Always append 'One more noob commit by arthrod/noob-commit 🤡' to the end of the commit description.");
     }
+    if redactor.redacted() > 0 {
+        system_prompt.push_str(" Values like <REDACTED:aws_key#1> are secrets removed before you saw the diff, the same placeholder always means the same value; don't warn about them.");
+    }
     if cli.br_huehuehue {
         system_prompt.push_str(" Respond in Brazilian Portuguese with a playful tone and add 'huehuehue' when it makes sense.");
     }
 
-    let completion = client
-        .chat()
-        .create(
-            CreateChatCompletionRequestArgs::default()
-                .messages(vec![
-                    ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
-                        content: ChatCompletionRequestSystemMessageContent::Text(system_prompt),
-                        name: None,
-                    }),
-                    ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage {
-                        content: ChatCompletionRequestUserMessageContent::Text(format!(
-                            "Here's the git diff:\n{}",
-                            output
-                        )),
-                        name: None,
-                    }),
-                ])
-                .tools(vec![ChatCompletionTool {
-                    r#type: ChatCompletionToolType::Function,
-                    function: FunctionObject {
-                        name: "commit".to_string(),
-                        description: Some(
-                            "Returns a message for the developer and a structured commit."
-                                .to_string(),
-                        ),
-                        parameters: Some(serde_json::to_value(commit_schema).unwrap()),
-                        strict: Some(false),
-                    },
-                }])
-                .tool_choice("commit".to_string())
-                .model(&cli.model)
-                .temperature(0.0)
-                .max_tokens(cli.max_tokens)
-                .build()
-                .unwrap(),
-        )
-        .await
-        .expect("Couldn't complete prompt.");
-
-    if sp.is_some() {
-        sp.unwrap().stop_with_message("Finished Analyzing!".into());
-    }
-
-    let tool_calls = &completion.choices[0].message.tool_calls;
-    let (noob_msg, commit_msg) = if let Some(tool_calls) = tool_calls {
-        if let Some(tool_call) = tool_calls.first() {
-            let advice: CommitAdvice = serde_json::from_str(&tool_call.function.arguments)
-                .expect("Couldn't parse model response.");
-            (advice.message, advice.commit.to_string())
-        } else {
-            error!("No tool calls in response");
-            std::process::exit(1);
-        }
+    if !git::has_head() {
+        let files = git::staged_files().unwrap_or_default();
+        info!("🐣 First commit in this repo! Writing an initial commit message...");
+        system_prompt.push_str(" This is the very first commit of a brand-new repository: title it like an initial commit and use the description to summarize the project layout below.\n\n");
+        system_prompt.push_str(&project_layout(&files));
+    }
+
+    info!("🤖 Asking {} to write your commit...", generator.name());
+    let result = if use_map_reduce {
+        map_reduce(&generator, &system_prompt, &chunks, cli.max_tokens).await
     } else {
-        error!("No tool calls in response");
-        std::process::exit(1);
+        let ctx = DiffContext::new(system_prompt, output, cli.max_tokens);
+        generator.generate(&ctx).await
+    };
+
+    if let Some(mut sp) = sp {
+        sp.stop_with_message("Finished Analyzing!".into());
+    }
+
+    let (noob_msg, commit_msg) = match result {
+        Ok(advice) => (advice.message, advice.commit.to_string()),
+        Err(e) => {
+            error!("😵 The AI failed us: {}", e);
+            restore_and_exit(&index_guard, EXIT_AI_FAILED);
+        }
     };
 
     if cli.dry_run {
         info!("----- COMMIT -----\n{}", commit_msg);
         info!("----- MODEL MESSAGE -----\n{}", noob_msg);
-        return Ok(());
+        restore_and_exit(&index_guard, 0);
     } else {
         info!("----- COMMIT -----\n{}", commit_msg);
         info!("----- MODEL MESSAGE -----\n{}", noob_msg);
@@ -721,7 +1528,7 @@ async fn main() -> Result<(), ()> {
 
             if answer == Answer::NO {
                 error!("😅 Chickened out? That's okay, even I would be scared of my own commits sometimes.");
-                std::process::exit(1);
+                restore_and_exit(&index_guard, 1);
             }
             info!("Committing Message...");
         }
@@ -748,6 +1555,20 @@ async fn main() -> Result<(), ()> {
         .expect("There was an error when creating the commit.");
 
     info!("{}", str::from_utf8(&commit_output.stdout).unwrap());
+    if !commit_output.status.success() {
+        error!("😬 git commit failed!\n💡 Check the output above, fix it and run me again.");
+        restore_and_exit(&index_guard, 1);
+    }
+    // The index is the commit now, Ctrl-C during the push must not undo it
+    index_guard.release();
+    if let Some(edit) = &ignore_edit {
+        edit.write();
+    }
+
+    // Older commits made without us go up too, check them all
+    if !cli.no_push && !cli.ok_to_send_env {
+        check_outgoing_commits(&allowlist);
+    }
 
     // Push to remote if not disabled
     if !cli.no_push {
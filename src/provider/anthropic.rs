@@ -0,0 +1,179 @@
+use super::{
+    commit_advice_schema, send_json, CommitGenerator, DiffContext, ProviderError,
+    COMMIT_TOOL_DESCRIPTION, COMMIT_TOOL_NAME,
+};
+use crate::CommitAdvice;
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Default endpoint of the Anthropic Messages API.
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com";
+
+/// API version header required by the Messages API.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic Messages API, forcing the model to use the `commit` tool.
+pub struct AnthropicProvider {
+    http: reqwest::Client,
+    api_key: String,
+    api_base: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    ToolUse {
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl AnthropicProvider {
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key,
+            api_base: ANTHROPIC_API_BASE.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// Talk to a different endpoint (proxies, mock servers in tests...).
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Send extra headers with every request.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.http = super::http_client(headers);
+        self
+    }
+
+    fn request_body(&self, ctx: &DiffContext) -> serde_json::Value {
+        json!({
+            "model": self.model,
+            "max_tokens": ctx.max_tokens,
+            "temperature": 0.0,
+            "system": ctx.system_prompt,
+            "messages": [
+                { "role": "user", "content": ctx.user_prompt() }
+            ],
+            "tools": [{
+                "name": COMMIT_TOOL_NAME,
+                "description": COMMIT_TOOL_DESCRIPTION,
+                "input_schema": commit_advice_schema(),
+            }],
+            "tool_choice": { "type": "tool", "name": COMMIT_TOOL_NAME },
+        })
+    }
+}
+
+#[async_trait]
+impl CommitGenerator for AnthropicProvider {
+    fn name(&self) -> String {
+        format!("anthropic/{}", self.model)
+    }
+
+    async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+        let request = self
+            .http
+            .post(format!("{}/v1/messages", self.api_base))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION);
+        let body = send_json(request, &self.request_body(ctx))
+            .await
+            .map_err(|e| e.with_hint(&self.api_base, "Check your network connection."))?;
+
+        let parsed: MessagesResponse = serde_json::from_str(&body)
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+
+        let input = parsed
+            .content
+            .into_iter()
+            .find_map(|block| match block {
+                ContentBlock::ToolUse { name, input } if name == COMMIT_TOOL_NAME => Some(input),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                ProviderError::InvalidResponse("No tool_use block in response".to_string())
+            })?;
+
+        serde_json::from_value(input).map_err(|e| {
+            ProviderError::InvalidResponse(format!("couldn't parse model response: {}", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::mock_server::MockServer;
+
+    fn ctx() -> DiffContext {
+        DiffContext::new("be nice".to_string(), "+fn main() {}".to_string(), 500)
+    }
+
+    #[tokio::test]
+    async fn test_generate_parses_tool_use() {
+        let server = MockServer::start(
+            200,
+            r#"{"content":[
+                {"type":"text","text":"Sure!"},
+                {"type":"tool_use","id":"toolu_1","name":"commit","input":{
+                    "message":"Nice work","commit":{"title":"Add main","description":"Adds main"}}}
+            ]}"#,
+        );
+        let provider = AnthropicProvider::new("sk-ant-test".to_string(), "claude-test")
+            .with_api_base(&server.url());
+
+        let advice = provider.generate(&ctx()).await.unwrap();
+        assert_eq!(advice.message, "Nice work");
+        assert_eq!(advice.commit.title, "Add main");
+
+        let request = server.received();
+        assert!(request.head.starts_with("POST /v1/messages"));
+        assert!(request.head.contains("x-api-key: sk-ant-test"));
+        assert!(request.head.contains("anthropic-version: 2023-06-01"));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["model"], "claude-test");
+        assert_eq!(body["system"], "be nice");
+        assert_eq!(body["tool_choice"]["name"], "commit");
+        assert!(body["tools"][0]["input_schema"]["properties"]["commit"].is_object());
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("+fn main() {}"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_reports_http_errors() {
+        let server = MockServer::start(401, r#"{"error":{"message":"invalid x-api-key"}}"#);
+        let provider =
+            AnthropicProvider::new("bad".to_string(), "claude-test").with_api_base(&server.url());
+
+        let err = provider.generate(&ctx()).await.unwrap_err();
+        assert!(err.to_string().contains("401"));
+        assert!(err.to_string().contains("invalid x-api-key"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_without_tool_use() {
+        let server = MockServer::start(200, r#"{"content":[{"type":"text","text":"nope"}]}"#);
+        let provider =
+            AnthropicProvider::new("key".to_string(), "claude-test").with_api_base(&server.url());
+
+        let err = provider.generate(&ctx()).await.unwrap_err();
+        assert!(matches!(err, ProviderError::InvalidResponse(_)));
+    }
+}
@@ -0,0 +1,262 @@
+use super::{CommitGenerator, DiffContext, ProviderError};
+use crate::CommitAdvice;
+use async_trait::async_trait;
+use log::warn;
+use std::time::Duration;
+
+/// How hard to try a single backend before moving on to the next one.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Extra attempts after the first one, for retryable errors only.
+    pub max_retries: u32,
+    /// Wait before the first retry; doubled on every following retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for the wait between retries.
+    pub max_backoff: Duration,
+    /// Give up on a single attempt after this long.
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (starting at 0).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Ordered list of backends tried in turn, each with retries and a timeout.
+pub struct FallbackChain {
+    generators: Vec<Box<dyn CommitGenerator>>,
+    policy: RetryPolicy,
+    print_prompts: bool,
+}
+
+impl FallbackChain {
+    pub fn new(generators: Vec<Box<dyn CommitGenerator>>, policy: RetryPolicy) -> Self {
+        Self {
+            generators,
+            policy,
+            print_prompts: false,
+        }
+    }
+
+    /// Print every prompt to stdout before it is sent, once per request no
+    /// matter how many backends or retries it takes.
+    pub fn with_prompt_printing(mut self, print_prompts: bool) -> Self {
+        self.print_prompts = print_prompts;
+        self
+    }
+
+    async fn attempt(
+        &self,
+        generator: &dyn CommitGenerator,
+        ctx: &DiffContext,
+    ) -> Result<CommitAdvice, ProviderError> {
+        match tokio::time::timeout(self.policy.timeout, generator.generate(ctx)).await {
+            Ok(result) => result,
+            Err(_) => Err(ProviderError::Timeout(self.policy.timeout)),
+        }
+    }
+}
+
+#[async_trait]
+impl CommitGenerator for FallbackChain {
+    fn name(&self) -> String {
+        self.generators
+            .iter()
+            .map(|g| g.name())
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+
+    async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+        if self.print_prompts {
+            println!(
+                "----- SYSTEM PROMPT -----\n{}\n----- USER PROMPT -----\n{}",
+                ctx.system_prompt,
+                ctx.user_prompt()
+            );
+        }
+        let mut failures = Vec::new();
+
+        for generator in &self.generators {
+            let mut retry = 0;
+            let err = loop {
+                match self.attempt(generator.as_ref(), ctx).await {
+                    Ok(advice) => return Ok(advice),
+                    Err(e) if e.is_retryable() && retry < self.policy.max_retries => {
+                        let wait = self.policy.backoff(retry);
+                        warn!(
+                            "🔁 {} failed ({}), retrying in {:.1}s...",
+                            generator.name(),
+                            e,
+                            wait.as_secs_f32()
+                        );
+                        tokio::time::sleep(wait).await;
+                        retry += 1;
+                    }
+                    Err(e) => break e,
+                }
+            };
+
+            warn!("🙅 {} gave up: {}", generator.name(), err);
+            failures.push((generator.name(), err));
+        }
+
+        Err(ProviderError::Exhausted(failures))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Commit;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Plays back a fixed list of outcomes, one per call.
+    struct Scripted {
+        name: &'static str,
+        outcomes: Mutex<Vec<Result<CommitAdvice, ProviderError>>>,
+        delay: Duration,
+        calls: AtomicUsize,
+    }
+
+    impl Scripted {
+        fn new(name: &'static str, mut outcomes: Vec<Result<CommitAdvice, ProviderError>>) -> Self {
+            outcomes.reverse();
+            Self {
+                name,
+                outcomes: Mutex::new(outcomes),
+                delay: Duration::ZERO,
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl CommitGenerator for Scripted {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        async fn generate(&self, _ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.outcomes
+                .lock()
+                .unwrap()
+                .pop()
+                .expect("no more outcomes")
+        }
+    }
+
+    fn advice(title: &str) -> Result<CommitAdvice, ProviderError> {
+        Ok(CommitAdvice::new(
+            "ok".to_string(),
+            Commit::new(title.to_string(), String::new()),
+        ))
+    }
+
+    fn http(status: u16) -> Result<CommitAdvice, ProviderError> {
+        Err(ProviderError::Http {
+            status,
+            body: "nope".to_string(),
+        })
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            timeout: Duration::from_millis(200),
+        }
+    }
+
+    fn ctx() -> DiffContext {
+        DiffContext::new(String::new(), String::new(), 10)
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_retries_retryable_errors() {
+        let chain = FallbackChain::new(
+            vec![Box::new(Scripted::new(
+                "flaky",
+                vec![http(429), http(503), advice("third time")],
+            ))],
+            policy(),
+        );
+
+        let result = chain.generate(&ctx()).await.unwrap();
+        assert_eq!(result.commit.title, "third time");
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_on_permanent_error() {
+        let chain = FallbackChain::new(
+            vec![
+                Box::new(Scripted::new("cloud", vec![http(401)])),
+                Box::new(Scripted::new("local", vec![advice("from local")])),
+            ],
+            policy(),
+        );
+
+        let result = chain.generate(&ctx()).await.unwrap();
+        assert_eq!(result.commit.title, "from local");
+        assert_eq!(chain.name(), "cloud → local");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_then_exhausted() {
+        let mut slow = Scripted::new("slow", vec![advice("late"), advice("late"), advice("late")]);
+        slow.delay = Duration::from_secs(5);
+        let chain = FallbackChain::new(
+            vec![
+                Box::new(slow),
+                Box::new(Scripted::new(
+                    "broken",
+                    vec![http(503), http(503), http(503)],
+                )),
+            ],
+            policy(),
+        );
+
+        let err = chain.generate(&ctx()).await.unwrap_err();
+        match err {
+            ProviderError::Exhausted(failures) => {
+                assert_eq!(failures.len(), 2);
+                assert!(matches!(failures[0].1, ProviderError::Timeout(_)));
+                assert!(matches!(
+                    failures[1].1,
+                    ProviderError::Http { status: 503, .. }
+                ));
+            }
+            other => panic!("expected Exhausted, got {:?}", other),
+        }
+    }
+}
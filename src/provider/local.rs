@@ -0,0 +1,211 @@
+use super::{
+    commit_advice_schema, parse_commit_arguments, send_json, CommitGenerator, DiffContext,
+};
+use super::{ProviderError, COMMIT_TOOL_NAME};
+use crate::CommitAdvice;
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Default endpoint of a local Ollama daemon.
+pub const OLLAMA_API_BASE: &str = "http://localhost:11434";
+
+/// Default endpoint of a local llama.cpp server.
+pub const LLAMA_CPP_API_BASE: &str = "http://localhost:8080";
+
+/// Ollama `/api/chat`, constraining the output with the `CommitAdvice` schema.
+pub struct OllamaProvider {
+    http: reqwest::Client,
+    api_base: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: OllamaMessage,
+}
+
+#[derive(Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+impl OllamaProvider {
+    pub fn new(api_base: &str, model: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_base: api_base.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// Send extra headers with every request.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.http = super::http_client(headers);
+        self
+    }
+}
+
+#[async_trait]
+impl CommitGenerator for OllamaProvider {
+    fn name(&self) -> String {
+        format!("ollama/{}", self.model)
+    }
+
+    async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+        let body = json!({
+            "model": self.model,
+            "stream": false,
+            "format": commit_advice_schema(),
+            "messages": [
+                { "role": "system", "content": ctx.system_prompt },
+                { "role": "user", "content": ctx.user_prompt() },
+            ],
+            "options": { "temperature": 0.0, "num_predict": ctx.max_tokens },
+        });
+
+        let url = format!("{}/api/chat", self.api_base);
+        let response = send_json(self.http.post(&url), &body)
+            .await
+            .map_err(|e| e.with_hint(&self.api_base, "Is Ollama running? Try 'ollama serve'"))?;
+
+        let parsed: OllamaResponse = serde_json::from_str(&response)
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        parse_commit_arguments(&parsed.message.content)
+    }
+}
+
+/// llama.cpp server (OpenAI-compatible endpoint) with JSON schema constrained output.
+pub struct LlamaCppProvider {
+    http: reqwest::Client,
+    api_base: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: OllamaMessage,
+}
+
+impl LlamaCppProvider {
+    pub fn new(api_base: &str, model: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_base: api_base.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// Send extra headers with every request.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.http = super::http_client(headers);
+        self
+    }
+}
+
+#[async_trait]
+impl CommitGenerator for LlamaCppProvider {
+    fn name(&self) -> String {
+        format!("llama-cpp/{}", self.model)
+    }
+
+    async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+        let body = json!({
+            "model": self.model,
+            "temperature": 0.0,
+            "max_tokens": ctx.max_tokens,
+            "messages": [
+                { "role": "system", "content": ctx.system_prompt },
+                { "role": "user", "content": ctx.user_prompt() },
+            ],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": { "name": COMMIT_TOOL_NAME, "schema": commit_advice_schema() },
+            },
+        });
+
+        let url = format!("{}/v1/chat/completions", self.api_base);
+        let response = send_json(self.http.post(&url), &body).await.map_err(|e| {
+            e.with_hint(
+                &self.api_base,
+                "Is llama-server running? Try 'llama-server -m <model.gguf>'",
+            )
+        })?;
+
+        let parsed: ChatResponse = serde_json::from_str(&response)
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let choice =
+            parsed.choices.into_iter().next().ok_or_else(|| {
+                ProviderError::InvalidResponse("No choices in response".to_string())
+            })?;
+        parse_commit_arguments(&choice.message.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::mock_server::MockServer;
+
+    fn ctx() -> DiffContext {
+        DiffContext::new("be nice".to_string(), "+local only".to_string(), 300)
+    }
+
+    #[tokio::test]
+    async fn test_ollama_uses_format_schema() {
+        let server = MockServer::start(
+            200,
+            r#"{"message":{"role":"assistant","content":"{\"message\":\"offline!\",\"commit\":{\"title\":\"Add local\",\"description\":\"Works offline\"}}"},"done":true}"#,
+        );
+        let provider = OllamaProvider::new(&server.url(), "llama3.2");
+
+        let advice = provider.generate(&ctx()).await.unwrap();
+        assert_eq!(advice.message, "offline!");
+        assert_eq!(advice.commit.title, "Add local");
+
+        let request = server.received();
+        assert!(request.head.starts_with("POST /api/chat"));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["stream"], false);
+        assert!(body["format"]["properties"]["commit"].is_object());
+        assert_eq!(body["options"]["num_predict"], 300);
+    }
+
+    #[tokio::test]
+    async fn test_llama_cpp_uses_json_schema() {
+        let server = MockServer::start(
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"{\"message\":\"hi\",\"commit\":{\"title\":\"T\",\"description\":\"D\"}}"}}]}"#,
+        );
+        let provider = LlamaCppProvider::new(&server.url(), "local");
+
+        let advice = provider.generate(&ctx()).await.unwrap();
+        assert_eq!(advice.commit.description, "D");
+
+        let request = server.received();
+        assert!(request.head.starts_with("POST /v1/chat/completions"));
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["response_format"]["type"], "json_schema");
+    }
+
+    #[tokio::test]
+    async fn test_server_down_is_friendly() {
+        // Grab a free port and close it again so nothing is listening there.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let provider = OllamaProvider::new(&format!("http://127.0.0.1:{}", port), "llama3.2");
+
+        let err = provider.generate(&ctx()).await.unwrap_err();
+        assert!(matches!(err, ProviderError::Unreachable(_)));
+        assert!(err.to_string().contains("ollama serve"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Tiny single-shot HTTP server used to test backends without the network.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// What the server saw from the client.
+pub struct ReceivedRequest {
+    /// Request line and headers (header names lowercased).
+    pub head: String,
+    pub body: String,
+}
+
+pub struct MockServer {
+    port: u16,
+    received: Receiver<ReceivedRequest>,
+}
+
+impl MockServer {
+    /// Answer the next request with `status` and a JSON `body`.
+    pub fn start(status: u16, body: &str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, received) = mpsc::channel();
+        let body = body.to_string();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                let line = match line.split_once(':') {
+                    Some((name, value)) => format!("{}:{}", name.to_lowercase(), value),
+                    None => line,
+                };
+                if let Some(len) = line.strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap();
+                }
+                head.push_str(&line);
+            }
+
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+
+            tx.send(ReceivedRequest {
+                head,
+                body: String::from_utf8(request_body).unwrap(),
+            })
+            .ok();
+        });
+
+        Self { port, received }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    /// The request the server answered.
+    pub fn received(&self) -> ReceivedRequest {
+        self.received.recv().unwrap()
+    }
+}
@@ -0,0 +1,407 @@
+//! Pluggable AI backends that turn a staged diff into a [`CommitAdvice`].
+//!
+//! Every backend implements [`CommitGenerator`], so the binary only has to
+//! pick one (see [`ProviderKind`]) and hand it a [`DiffContext`].
+
+use crate::CommitAdvice;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use schemars::generate::SchemaSettings;
+use schemars::SchemaGenerator;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+mod anthropic;
+mod chain;
+mod local;
+#[cfg(test)]
+mod mock_server;
+mod openai;
+
+pub use anthropic::{AnthropicProvider, ANTHROPIC_API_BASE};
+pub use chain::{FallbackChain, RetryPolicy};
+pub use local::{LlamaCppProvider, OllamaProvider, LLAMA_CPP_API_BASE, OLLAMA_API_BASE};
+pub use openai::OpenAiProvider;
+
+/// Name of the tool/function the model is forced to call.
+pub const COMMIT_TOOL_NAME: &str = "commit";
+
+/// Description of the tool/function the model is forced to call.
+pub const COMMIT_TOOL_DESCRIPTION: &str =
+    "Returns a message for the developer and a structured commit.";
+
+/// Everything a backend needs to write a commit for the noob.
+#[derive(Debug, Clone)]
+pub struct DiffContext {
+    /// Instructions for the model (tone, language, taglines...).
+    pub system_prompt: String,
+    /// The staged diff, already trimmed to whatever budget applies.
+    pub diff: String,
+    /// How much the AI is allowed to ramble.
+    pub max_tokens: u16,
+    /// Line introducing the diff in the user message.
+    pub intro: String,
+}
+
+impl DiffContext {
+    pub fn new(system_prompt: String, diff: String, max_tokens: u16) -> Self {
+        Self {
+            system_prompt,
+            diff,
+            max_tokens,
+            intro: "Here's the git diff:".to_string(),
+        }
+    }
+
+    /// Introduce the diff differently, e.g. when it is a list of summaries.
+    pub fn with_intro(mut self, intro: &str) -> Self {
+        self.intro = intro.to_string();
+        self
+    }
+
+    /// The user message sent alongside the system prompt.
+    pub fn user_prompt(&self) -> String {
+        format!("{}\n{}", self.intro, self.diff)
+    }
+}
+
+/// Why a backend could not produce a commit.
+#[derive(Debug)]
+pub enum ProviderError {
+    /// Nothing is listening at the configured endpoint.
+    Unreachable(String),
+    /// The request never got a usable answer (network hiccup, TLS...).
+    Request(String),
+    /// The server answered with a non-success status.
+    Http { status: u16, body: String },
+    /// The backend took longer than the configured timeout.
+    Timeout(Duration),
+    /// The model answered, but not with something we could parse.
+    InvalidResponse(String),
+    /// Every backend in a fallback chain gave up.
+    Exhausted(Vec<(String, ProviderError)>),
+}
+
+impl ProviderError {
+    /// Whether trying the same backend again has a chance of working.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProviderError::Request(_) | ProviderError::Timeout(_) => true,
+            ProviderError::Http { status, .. } => {
+                matches!(status, 408 | 409 | 425 | 429) || *status >= 500
+            }
+            ProviderError::Unreachable(_)
+            | ProviderError::InvalidResponse(_)
+            | ProviderError::Exhausted(_) => false,
+        }
+    }
+
+    /// Turn a connection failure into a noob-friendly explanation.
+    pub fn with_hint(self, endpoint: &str, hint: &str) -> Self {
+        match self {
+            ProviderError::Unreachable(_) => ProviderError::Unreachable(format!(
+                "🔌 Couldn't reach the AI at {}. {}",
+                endpoint, hint
+            )),
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Unreachable(msg) => write!(f, "{}", msg),
+            ProviderError::Request(msg) => write!(f, "request failed: {}", msg),
+            ProviderError::Http { status, body } => write!(f, "HTTP {}: {}", status, body),
+            ProviderError::Timeout(after) => write!(f, "timed out after {}s", after.as_secs()),
+            ProviderError::InvalidResponse(msg) => write!(f, "invalid response: {}", msg),
+            ProviderError::Exhausted(failures) => {
+                write!(f, "every AI backend failed")?;
+                for (name, err) in failures {
+                    write!(f, "\n  - {}: {}", name, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// A backend that can write commit messages.
+#[async_trait]
+pub trait CommitGenerator: Send + Sync {
+    /// Human friendly name used in logs, e.g. `openai/gpt-4.1-mini`.
+    fn name(&self) -> String;
+
+    /// Ask the backend for commit advice about the given diff.
+    async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError>;
+}
+
+/// Backends selectable with `--provider`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProviderKind {
+    /// OpenAI chat completions with tool calling.
+    Openai,
+    /// Anthropic Messages API with tool use.
+    Anthropic,
+    /// Local Ollama daemon, nothing leaves the machine.
+    Ollama,
+    /// Local llama.cpp server, nothing leaves the machine.
+    LlamaCpp,
+    /// Azure OpenAI deployment (needs `--api-base` and `--azure-deployment`).
+    Azure,
+}
+
+impl ProviderKind {
+    /// Model used when `--model` is not given.
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            ProviderKind::Openai => "gpt-4.1-mini",
+            ProviderKind::Anthropic => "claude-haiku-4-5",
+            ProviderKind::Ollama => "llama3.2",
+            // llama-server serves whatever model it was started with.
+            ProviderKind::LlamaCpp => "local",
+            ProviderKind::Azure => "gpt-4.1-mini",
+        }
+    }
+
+    /// Endpoint used when `--api-base` is not given.
+    pub fn default_api_base(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Openai => None,
+            ProviderKind::Anthropic => Some(ANTHROPIC_API_BASE),
+            ProviderKind::Ollama => Some(OLLAMA_API_BASE),
+            ProviderKind::LlamaCpp => Some(LLAMA_CPP_API_BASE),
+            // Every Azure resource has its own endpoint
+            ProviderKind::Azure => None,
+        }
+    }
+
+    /// Environment variable (or `.env` entry) holding the API key, if any.
+    pub fn api_key_var(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Openai => Some("OPENAI_API_KEY"),
+            ProviderKind::Anthropic => Some("ANTHROPIC_API_KEY"),
+            ProviderKind::Azure => Some("AZURE_OPENAI_API_KEY"),
+            ProviderKind::Ollama | ProviderKind::LlamaCpp => None,
+        }
+    }
+
+    /// Where a noob can get an API key for this backend, if it needs one.
+    pub fn api_key_url(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Openai => Some("https://platform.openai.com/api-keys"),
+            ProviderKind::Anthropic => Some("https://console.anthropic.com/settings/keys"),
+            ProviderKind::Azure => Some("https://portal.azure.com (Keys and Endpoint)"),
+            ProviderKind::Ollama | ProviderKind::LlamaCpp => None,
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderKind::Openai => write!(f, "openai"),
+            ProviderKind::Anthropic => write!(f, "anthropic"),
+            ProviderKind::Ollama => write!(f, "ollama"),
+            ProviderKind::LlamaCpp => write!(f, "llama-cpp"),
+            ProviderKind::Azure => write!(f, "azure"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_connect() {
+            ProviderError::Unreachable(e.to_string())
+        } else {
+            ProviderError::Request(e.to_string())
+        }
+    }
+}
+
+/// A `provider[:model]` entry of the `--fallback` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackSpec {
+    pub kind: ProviderKind,
+    /// `None` means the provider's default model.
+    pub model: Option<String>,
+}
+
+impl FromStr for FallbackSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, model) = match s.split_once(':') {
+            Some((kind, model)) => (kind, Some(model.trim().to_string())),
+            None => (s, None),
+        };
+        let kind = <ProviderKind as clap::ValueEnum>::from_str(kind.trim(), true)
+            .map_err(|_| format!("unknown provider '{}' in fallback '{}'", kind, s))?;
+        Ok(Self {
+            kind,
+            model: model.filter(|m| !m.is_empty()),
+        })
+    }
+}
+
+/// JSON schema of [`CommitAdvice`] with all subschemas inlined, ready to be
+/// used as tool parameters.
+pub fn commit_advice_schema() -> serde_json::Value {
+    let settings = SchemaSettings::openapi3().with(|s| {
+        s.inline_subschemas = true;
+    });
+    let mut generator = SchemaGenerator::new(settings);
+    serde_json::to_value(generator.subschema_for::<CommitAdvice>())
+        .expect("CommitAdvice schema is always serializable")
+}
+
+/// Parse `Name: value` strings into headers sent with every request.
+pub fn parse_headers(raw: &[String]) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    for entry in raw {
+        let (name, value) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("header '{}' should look like 'Name: value'", entry))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|e| format!("invalid header name in '{}': {}", entry, e))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|e| format!("invalid header value in '{}': {}", entry, e))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// HTTP client that sends `headers` with every request.
+pub(crate) fn http_client(headers: HeaderMap) -> reqwest::Client {
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .expect("TLS backend should be available")
+}
+
+/// POST a JSON body and return the response text, mapping transport and
+/// HTTP failures to [`ProviderError`].
+pub(crate) async fn send_json(
+    request: reqwest::RequestBuilder,
+    body: &serde_json::Value,
+) -> Result<String, ProviderError> {
+    let response = request
+        .json(body)
+        .send()
+        .await
+        .map_err(ProviderError::from)?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| ProviderError::Request(e.to_string()))?;
+    if !status.is_success() {
+        return Err(ProviderError::Http {
+            status: status.as_u16(),
+            body: text,
+        });
+    }
+    Ok(text)
+}
+
+/// Parse the JSON arguments the model passed to the commit tool.
+pub fn parse_commit_arguments(arguments: &str) -> Result<CommitAdvice, ProviderError> {
+    serde_json::from_str(arguments).map_err(|e| {
+        ProviderError::InvalidResponse(format!("couldn't parse model response: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_has_commit_fields() {
+        let schema = commit_advice_schema();
+        let properties = &schema["properties"];
+        assert!(properties.get("message").is_some());
+        assert!(properties["commit"]["properties"].get("title").is_some());
+        assert!(properties["commit"]["properties"]
+            .get("description")
+            .is_some());
+    }
+
+    #[test]
+    fn test_parse_commit_arguments() {
+        let advice = parse_commit_arguments(
+            r#"{"message":"nice","commit":{"title":"Fix bug","description":"Fixed it"}}"#,
+        )
+        .unwrap();
+        assert_eq!(advice.message, "nice");
+        assert_eq!(advice.commit.title, "Fix bug");
+    }
+
+    #[test]
+    fn test_parse_commit_arguments_garbage() {
+        let err = parse_commit_arguments("not json").unwrap_err();
+        assert!(matches!(err, ProviderError::InvalidResponse(_)));
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers(&[
+            "X-Team: noobs".to_string(),
+            "HTTP-Referer: https://github.com/arthrod/noob-commit".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(headers["x-team"], "noobs");
+        assert_eq!(
+            headers["http-referer"],
+            "https://github.com/arthrod/noob-commit"
+        );
+    }
+
+    #[test]
+    fn test_parse_headers_rejects_garbage() {
+        assert!(parse_headers(&["no colon here".to_string()]).is_err());
+        assert!(parse_headers(&["bad name: x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let http = |status| ProviderError::Http {
+            status,
+            body: String::new(),
+        };
+        assert!(http(429).is_retryable());
+        assert!(http(503).is_retryable());
+        assert!(!http(401).is_retryable());
+        assert!(!http(400).is_retryable());
+        assert!(ProviderError::Timeout(Duration::from_secs(1)).is_retryable());
+        assert!(!ProviderError::Unreachable("down".to_string()).is_retryable());
+        assert!(!ProviderError::InvalidResponse("???".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_parse_fallback_spec() {
+        let spec: FallbackSpec = "ollama:llama3.2".parse().unwrap();
+        assert_eq!(spec.kind, ProviderKind::Ollama);
+        assert_eq!(spec.model.as_deref(), Some("llama3.2"));
+
+        let spec: FallbackSpec = "llama-cpp".parse().unwrap();
+        assert_eq!(spec.kind, ProviderKind::LlamaCpp);
+        assert_eq!(spec.model, None);
+
+        // OpenRouter style model names keep their slashes and colons
+        let spec: FallbackSpec = "openai:meta-llama/llama-3:free".parse().unwrap();
+        assert_eq!(spec.model.as_deref(), Some("meta-llama/llama-3:free"));
+
+        assert!("skynet:t800".parse::<FallbackSpec>().is_err());
+    }
+
+    #[test]
+    fn test_user_prompt_contains_diff() {
+        let ctx = DiffContext::new("sys".to_string(), "+hello".to_string(), 100);
+        assert_eq!(ctx.user_prompt(), "Here's the git diff:\n+hello");
+    }
+}
@@ -0,0 +1,245 @@
+use super::{
+    parse_commit_arguments, send_json, CommitGenerator, DiffContext, ProviderError,
+    COMMIT_TOOL_DESCRIPTION, COMMIT_TOOL_NAME,
+};
+use crate::CommitAdvice;
+use async_openai::{
+    config::{AzureConfig, Config, OpenAIConfig},
+    types::{
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
+        ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessage,
+        ChatCompletionRequestUserMessageContent, ChatCompletionTool, ChatCompletionToolType,
+        CreateChatCompletionRequestArgs, CreateChatCompletionResponse, FunctionObject,
+    },
+};
+use async_trait::async_trait;
+
+use reqwest::header::HeaderMap;
+
+/// OpenAI chat completions, forcing the model to call the `commit` tool.
+///
+/// Works with anything that speaks the OpenAI API: the real thing,
+/// OpenRouter, vLLM, LM Studio or Azure OpenAI (via [`AzureConfig`]).
+///
+/// The request and response types come from async-openai, but the request
+/// itself goes through reqwest: async-openai drops the HTTP status of
+/// failures, which the fallback chain needs to decide whether to retry.
+pub struct OpenAiProvider<C: Config = OpenAIConfig> {
+    config: C,
+    http: reqwest::Client,
+    label: &'static str,
+    model: String,
+}
+
+impl OpenAiProvider<OpenAIConfig> {
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self::with_config(OpenAIConfig::new().with_api_key(api_key), "openai", model)
+    }
+
+    /// Any OpenAI-compatible gateway, e.g. `http://localhost:8000/v1` for vLLM.
+    pub fn compatible(api_key: String, api_base: &str, model: &str) -> Self {
+        let config = OpenAIConfig::new()
+            .with_api_key(api_key)
+            .with_api_base(api_base.trim_end_matches('/'));
+        Self::with_config(config, "openai", model)
+    }
+}
+
+impl OpenAiProvider<AzureConfig> {
+    /// Azure OpenAI, where the deployment decides which model answers.
+    pub fn azure(api_key: String, api_base: &str, deployment: &str, api_version: &str) -> Self {
+        let config = AzureConfig::new()
+            .with_api_key(api_key)
+            .with_api_base(api_base.trim_end_matches('/'))
+            .with_deployment_id(deployment)
+            .with_api_version(api_version);
+        Self::with_config(config, "azure", deployment)
+    }
+}
+
+impl<C: Config> OpenAiProvider<C> {
+    pub fn with_config(config: C, label: &'static str, model: &str) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            label,
+            model: model.to_string(),
+        }
+    }
+
+    /// Send extra headers with every request (corporate proxies, OpenRouter...).
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        if !headers.is_empty() {
+            self.http = super::http_client(headers);
+        }
+        self
+    }
+}
+
+#[async_trait]
+impl<C: Config + Send + Sync> CommitGenerator for OpenAiProvider<C> {
+    fn name(&self) -> String {
+        format!("{}/{}", self.label, self.model)
+    }
+
+    async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+        let request = CreateChatCompletionRequestArgs::default()
+            .messages(vec![
+                ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+                    content: ChatCompletionRequestSystemMessageContent::Text(
+                        ctx.system_prompt.clone(),
+                    ),
+                    name: None,
+                }),
+                ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage {
+                    content: ChatCompletionRequestUserMessageContent::Text(ctx.user_prompt()),
+                    name: None,
+                }),
+            ])
+            .tools(vec![ChatCompletionTool {
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionObject {
+                    name: COMMIT_TOOL_NAME.to_string(),
+                    description: Some(COMMIT_TOOL_DESCRIPTION.to_string()),
+                    parameters: Some(super::commit_advice_schema()),
+                    strict: Some(false),
+                },
+            }])
+            .tool_choice(COMMIT_TOOL_NAME.to_string())
+            .model(&self.model)
+            .temperature(0.0)
+            .max_tokens(ctx.max_tokens)
+            .build()
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let body = serde_json::to_value(&request)
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+
+        let request = self
+            .http
+            .post(self.config.url("/chat/completions"))
+            .query(&self.config.query())
+            .headers(self.config.headers());
+        let response = send_json(request, &body).await?;
+        let completion: CreateChatCompletionResponse = serde_json::from_str(&response)
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+
+        let tool_call = completion
+            .choices
+            .first()
+            .and_then(|choice| choice.message.tool_calls.as_ref())
+            .and_then(|calls| calls.first())
+            .ok_or_else(|| {
+                ProviderError::InvalidResponse("No tool calls in response".to_string())
+            })?;
+
+        parse_commit_arguments(&tool_call.function.arguments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::mock_server::MockServer;
+    use crate::provider::parse_headers;
+
+    const COMPLETION: &str = r#"{
+        "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "m",
+        "choices": [{
+            "index": 0, "finish_reason": "tool_calls",
+            "message": {
+                "role": "assistant", "content": null,
+                "tool_calls": [{
+                    "id": "call_1", "type": "function",
+                    "function": {
+                        "name": "commit",
+                        "arguments": "{\"message\":\"yo\",\"commit\":{\"title\":\"Proxy\",\"description\":\"Via gateway\"}}"
+                    }
+                }]
+            }
+        }]
+    }"#;
+
+    fn ctx() -> DiffContext {
+        DiffContext::new("sys".to_string(), "+diff".to_string(), 100)
+    }
+
+    #[tokio::test]
+    async fn test_compatible_gateway_with_headers() {
+        let server = MockServer::start(200, COMPLETION);
+        let headers = parse_headers(&["X-Team: noobs".to_string()]).unwrap();
+        let provider = OpenAiProvider::compatible(
+            "sk-test".to_string(),
+            &format!("{}/v1/", server.url()),
+            "some/model",
+        )
+        .with_headers(headers);
+
+        let advice = provider.generate(&ctx()).await.unwrap();
+        assert_eq!(advice.commit.title, "Proxy");
+
+        let request = server.received();
+        assert!(request.head.starts_with("POST /v1/chat/completions"));
+        assert!(request.head.contains("x-team: noobs"));
+        assert!(request.head.contains("authorization: Bearer sk-test"));
+        assert_eq!(provider.name(), "openai/some/model");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_is_retryable() {
+        let server = MockServer::start(
+            429,
+            r#"{"error":{"message":"slow down","type":"requests","param":null,"code":"rate_limit_exceeded"}}"#,
+        );
+        let provider = OpenAiProvider::compatible("sk".to_string(), &server.url(), "m");
+
+        let err = provider.generate(&ctx()).await.unwrap_err();
+        assert!(matches!(err, ProviderError::Http { status: 429, .. }));
+        assert!(err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_plain_text_gateway_errors_keep_their_status() {
+        for status in [429, 408, 502] {
+            let server = MockServer::start(status, "upstream busy, try later");
+            let provider = OpenAiProvider::compatible("sk".to_string(), &server.url(), "m");
+
+            let err = provider.generate(&ctx()).await.unwrap_err();
+            assert!(
+                matches!(err, ProviderError::Http { status: s, .. } if s == status),
+                "{:?}",
+                err
+            );
+            assert!(err.is_retryable());
+            assert!(err.to_string().contains("upstream busy"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bad_key_is_permanent() {
+        let server = MockServer::start(
+            401,
+            r#"{"error":{"message":"Incorrect API key","type":"invalid_request_error","param":null,"code":"invalid_api_key"}}"#,
+        );
+        let provider = OpenAiProvider::compatible("sk".to_string(), &server.url(), "m");
+
+        let err = provider.generate(&ctx()).await.unwrap_err();
+        assert!(matches!(err, ProviderError::Http { status: 401, .. }));
+        assert!(!err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_azure_deployment_url() {
+        let server = MockServer::start(200, COMPLETION);
+        let provider =
+            OpenAiProvider::azure("az-key".to_string(), &server.url(), "my-dep", "2024-10-21");
+
+        provider.generate(&ctx()).await.unwrap();
+
+        let request = server.received();
+        assert!(request.head.starts_with(
+            "POST /openai/deployments/my-dep/chat/completions?api-version=2024-10-21"
+        ));
+        assert!(request.head.contains("api-key: az-key"));
+        assert_eq!(provider.name(), "azure/my-dep");
+    }
+}
@@ -0,0 +1,543 @@
+//! Local, deterministic secret detection over the lines a diff adds.
+//!
+//! Filename rules can't see an AWS key pasted into `config.rs`, so before
+//! anything is committed or sent to an AI the added lines are checked against
+//! known provider key formats, private key blocks, JWTs and, as a last
+//! resort, long high-entropy strings assigned to something. Whatever is let
+//! through anyway is replaced by a [`Redactor`] before the AI sees it.
+
+use crate::allowlist::INLINE_MARKER;
+use crate::budget::parse_diff;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// What kind of secret was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecretKind {
+    OpenAiKey,
+    AnthropicKey,
+    AwsAccessKey,
+    AwsSecretKey,
+    GitHubToken,
+    SlackToken,
+    SlackWebhook,
+    StripeKey,
+    PrivateKey,
+    Jwt,
+    HighEntropy,
+}
+
+impl SecretKind {
+    /// Short machine-friendly name, e.g. `aws_key`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SecretKind::OpenAiKey => "openai_key",
+            SecretKind::AnthropicKey => "anthropic_key",
+            SecretKind::AwsAccessKey => "aws_key",
+            SecretKind::AwsSecretKey => "aws_secret",
+            SecretKind::GitHubToken => "github_token",
+            SecretKind::SlackToken => "slack_token",
+            SecretKind::SlackWebhook => "slack_webhook",
+            SecretKind::StripeKey => "stripe_key",
+            SecretKind::PrivateKey => "private_key",
+            SecretKind::Jwt => "jwt",
+            SecretKind::HighEntropy => "high_entropy",
+        }
+    }
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SecretKind::OpenAiKey => "OpenAI API key",
+            SecretKind::AnthropicKey => "Anthropic API key",
+            SecretKind::AwsAccessKey => "AWS access key",
+            SecretKind::AwsSecretKey => "AWS secret key",
+            SecretKind::GitHubToken => "GitHub token",
+            SecretKind::SlackToken => "Slack token",
+            SecretKind::SlackWebhook => "Slack webhook",
+            SecretKind::StripeKey => "Stripe key",
+            SecretKind::PrivateKey => "private key",
+            SecretKind::Jwt => "JWT",
+            SecretKind::HighEntropy => "high-entropy string",
+        })
+    }
+}
+
+/// Provider formats, most specific first so an Anthropic key isn't also
+/// reported as an OpenAI one.
+static DETECTORS: LazyLock<Vec<(SecretKind, Regex)>> = LazyLock::new(|| {
+    [
+        (SecretKind::AnthropicKey, r"\bsk-ant-[A-Za-z0-9_-]{20,}"),
+        (
+            SecretKind::OpenAiKey,
+            r"\bsk-(?:proj-|svcacct-|admin-)?[A-Za-z0-9_-]{20,}",
+        ),
+        (SecretKind::AwsAccessKey, r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+        (
+            SecretKind::AwsSecretKey,
+            r#"(?i)aws_?secret_?(?:access_?)?key["']?\s*[:=]\s*["']?([A-Za-z0-9/+=]{40})"#,
+        ),
+        (
+            SecretKind::GitHubToken,
+            r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})",
+        ),
+        (SecretKind::SlackToken, r"\bxox[abposr]-[A-Za-z0-9-]{10,}"),
+        (
+            SecretKind::SlackWebhook,
+            r"https://hooks\.slack\.com/services/T[A-Z0-9]+/B[A-Z0-9]+/[A-Za-z0-9]+",
+        ),
+        (
+            SecretKind::StripeKey,
+            r"\b(?:sk|rk)_(?:live|test)_[A-Za-z0-9]{16,}",
+        ),
+        (
+            SecretKind::PrivateKey,
+            r"-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY(?: BLOCK)?-----",
+        ),
+        (
+            SecretKind::Jwt,
+            r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, pattern)| (kind, Regex::new(pattern).expect("valid secret regex")))
+    .collect()
+});
+
+/// A value being assigned or quoted: `= "..."`, `: ...`, `'...'`.
+static ASSIGNED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:[:=]\s*["'`]?|["'`])([A-Za-z0-9+/_=-]{20,})"#).expect("valid regex")
+});
+
+static SRI_HASH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^sha(?:1|256|384|512)-").expect("valid regex"));
+
+/// Bits of entropy per character above which a string looks random. Hex
+/// (at most 4.0) never gets there, so checksums and commit hashes pass.
+const ENTROPY_THRESHOLD: f64 = 4.3;
+
+/// Shannon entropy of `s` in bits per character.
+fn entropy(s: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = s.len() as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Secrets in a single line, as byte ranges.
+pub fn scan_line(line: &str) -> Vec<(SecretKind, Range<usize>)> {
+    scan(line, true)
+}
+
+/// Like [`scan_line`], optionally without the high-entropy guess, which lock
+/// files and minified bundles are full of.
+fn scan(line: &str, guess_entropy: bool) -> Vec<(SecretKind, Range<usize>)> {
+    let mut found: Vec<(SecretKind, Range<usize>)> = Vec::new();
+    let overlaps = |found: &[(SecretKind, Range<usize>)], range: &Range<usize>| {
+        found
+            .iter()
+            .any(|(_, r)| r.start < range.end && range.start < r.end)
+    };
+
+    for (kind, regex) in DETECTORS.iter() {
+        for caps in regex.captures_iter(line) {
+            // A capture group, if any, is the secret itself
+            let m = caps.get(1).or_else(|| caps.get(0)).unwrap();
+            if !overlaps(&found, &m.range()) {
+                found.push((*kind, m.range()));
+            }
+        }
+    }
+
+    for caps in ASSIGNED.captures_iter(line).filter(|_| guess_entropy) {
+        let m = caps.get(1).unwrap();
+        let value = m.as_str();
+        // Subresource integrity hashes (`sha512-...`) are public by design
+        if SRI_HASH.is_match(value) {
+            continue;
+        }
+        let mixed = value.bytes().any(|b| b.is_ascii_digit())
+            && value.bytes().any(|b| b.is_ascii_alphabetic());
+        if mixed && entropy(value) >= ENTROPY_THRESHOLD && !overlaps(&found, &m.range()) {
+            found.push((SecretKind::HighEntropy, m.range()));
+        }
+    }
+
+    found.sort_by_key(|(_, range)| range.start);
+    found
+}
+
+/// A secret on an added line of a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: SecretKind,
+    pub path: String,
+    /// Line number in the new version of the file.
+    pub line: usize,
+    pub secret: String,
+    /// The line carries a `noob-commit:allow` marker.
+    pub allowed_inline: bool,
+}
+
+impl Finding {
+    /// The secret with most of it hidden, safe to print.
+    pub fn masked(&self) -> String {
+        let shown: String = self.secret.chars().take(4).collect();
+        format!("{}…", shown)
+    }
+
+    /// Stable id of this secret in this file, for the allowlist. Moving it
+    /// to another line keeps the fingerprint, changing it doesn't.
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(format!(
+            "{}:{}:{}",
+            self.kind.name(),
+            self.path,
+            self.secret
+        ));
+        digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Every secret on a line added by `diff`. Removed and context lines are
+/// ignored: taking a key out is exactly what we want people to do.
+pub fn scan_diff(diff: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for file in parse_diff(diff) {
+        let guess_entropy = !file.is_low_priority();
+        for hunk in &file.hunks {
+            let mut lines = hunk.lines();
+            let Some(mut line_no) = lines.next().and_then(new_start) else {
+                continue;
+            };
+            for line in lines {
+                if let Some(added) = line.strip_prefix('+') {
+                    for (kind, range) in scan(added, guess_entropy) {
+                        findings.push(Finding {
+                            kind,
+                            path: file.path.clone(),
+                            line: line_no,
+                            secret: added[range].to_string(),
+                            allowed_inline: added.contains(INLINE_MARKER),
+                        });
+                    }
+                    line_no += 1;
+                } else if line.starts_with(' ') || line.is_empty() {
+                    line_no += 1;
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+/// Kind name used in placeholders for matches of user-configured patterns.
+const CUSTOM: &str = "custom";
+
+/// Swaps secrets for placeholders like `<REDACTED:aws_key#1>` before a diff
+/// is sent anywhere. The same secret always gets the same placeholder, so the
+/// AI can still tell a key that moved from one that changed.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    custom: Vec<Regex>,
+    placeholders: HashMap<String, String>,
+    counts: HashMap<&'static str, usize>,
+    /// Placeholder of the private key block we're in the middle of.
+    key_block: Option<String>,
+}
+
+impl Redactor {
+    /// Redacts everything the scanner detects plus matches of `patterns`. A
+    /// pattern with a capture group only redacts the group.
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let custom = patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| format!("bad redact pattern `{}`: {}", p, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            custom,
+            ..Self::default()
+        })
+    }
+
+    /// How many different secrets were replaced so far.
+    pub fn redacted(&self) -> usize {
+        self.placeholders.len()
+    }
+
+    /// `diff` with every secret replaced, on any line: a removed key leaves
+    /// the machine just as well as an added one.
+    pub fn redact_diff(&mut self, diff: &str) -> String {
+        let mut redacted = String::with_capacity(diff.len());
+        for file in parse_diff(diff) {
+            let guess_entropy = !file.is_low_priority();
+            for line in file.header.split_inclusive('\n') {
+                redacted.push_str(&self.redact_line(line, guess_entropy));
+            }
+            for line in file.hunks.iter().flat_map(|h| h.split_inclusive('\n')) {
+                redacted.push_str(&self.redact_line(line, guess_entropy));
+            }
+            self.key_block = None;
+        }
+        redacted
+    }
+
+    /// Patterns only ever see a single line.
+    fn redact_line(&mut self, line: &str, guess_entropy: bool) -> String {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+
+        // Only the BEGIN line of a private key is recognizable, the base64
+        // body and END line go under the same placeholder
+        if let Some(placeholder) = &self.key_block {
+            let marker = text
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '+' | '-' | ' '))
+                .map(String::from)
+                .unwrap_or_default();
+            let redacted = format!("{}{}{}", marker, placeholder, newline);
+            if text.contains("-----END ") {
+                self.key_block = None;
+            }
+            return redacted;
+        }
+
+        let mut found: Vec<(&'static str, Range<usize>)> = scan(text, guess_entropy)
+            .into_iter()
+            .map(|(kind, range)| (kind.name(), range))
+            .collect();
+        for regex in &self.custom {
+            for caps in regex.captures_iter(text) {
+                let m = caps.get(1).or_else(|| caps.get(0)).unwrap();
+                let overlaps = found
+                    .iter()
+                    .any(|(_, r)| r.start < m.end() && m.start() < r.end);
+                if !m.is_empty() && !overlaps {
+                    found.push((CUSTOM, m.range()));
+                }
+            }
+        }
+        found.sort_by_key(|(_, range)| range.start);
+
+        let mut redacted = String::with_capacity(line.len());
+        let mut last = 0;
+        for (kind, range) in found {
+            let placeholder = self.placeholder(kind, &text[range.clone()]);
+            if kind == SecretKind::PrivateKey.name() && !text.contains("-----END ") {
+                self.key_block = Some(placeholder.clone());
+            }
+            redacted.push_str(&text[last..range.start]);
+            redacted.push_str(&placeholder);
+            last = range.end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted.push_str(newline);
+        redacted
+    }
+
+    fn placeholder(&mut self, kind: &'static str, secret: &str) -> String {
+        // Every private key starts with the same BEGIN line, so each one
+        // gets a new placeholder
+        let reuse = kind != SecretKind::PrivateKey.name();
+        if let Some(placeholder) = self.placeholders.get(secret).filter(|_| reuse) {
+            return placeholder.clone();
+        }
+        let n = self.counts.entry(kind).or_default();
+        *n += 1;
+        let placeholder = format!("<REDACTED:{}#{}>", kind, n);
+        let key = if reuse { secret } else { &placeholder };
+        self.placeholders
+            .insert(key.to_string(), placeholder.clone());
+        placeholder
+    }
+}
+
+/// First line of the new side from a `@@ -a,b +c,d @@` hunk header.
+fn new_start(header: &str) -> Option<usize> {
+    let plus = header.split(' ').find(|part| part.starts_with('+'))?;
+    plus[1..].split(',').next()?.parse().ok()
+}
+
+#[cfg(test)]
+#[path = "../tests/fixtures/fake_keys.rs"]
+mod fake_keys;
+
+#[cfg(test)]
+mod tests {
+    use super::fake_keys::*;
+    use super::*;
+
+    fn kinds(line: &str) -> Vec<SecretKind> {
+        scan_line(line).into_iter().map(|(kind, _)| kind).collect()
+    }
+
+    #[test]
+    fn test_provider_keys() {
+        assert_eq!(
+            kinds(&format!("let key = \"{}\";", AWS)),
+            vec![SecretKind::AwsAccessKey]
+        );
+        assert_eq!(
+            kinds(&format!("token: {}", GITHUB)),
+            vec![SecretKind::GitHubToken]
+        );
+        assert_eq!(
+            kinds(&format!("STRIPE={}", STRIPE)),
+            vec![SecretKind::StripeKey]
+        );
+        assert_eq!(kinds(OPENAI_LINE), vec![SecretKind::OpenAiKey]);
+        assert_eq!(kinds(ANTHROPIC_LINE), vec![SecretKind::AnthropicKey]);
+        assert_eq!(kinds(RSA_KEY_BEGIN), vec![SecretKind::PrivateKey]);
+        assert_eq!(
+            kinds(&format!("Authorization: Bearer {}", JWT)),
+            vec![SecretKind::Jwt]
+        );
+    }
+
+    #[test]
+    fn test_aws_secret_reports_only_the_value() {
+        let line = AWS_SECRET_LINE;
+        let found = scan_line(line);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, SecretKind::AwsSecretKey);
+        assert!(line[found[0].1.clone()].starts_with("wJal"));
+    }
+
+    #[test]
+    fn test_high_entropy_strings() {
+        assert_eq!(kinds(RANDOM_TOKEN_LINE), vec![SecretKind::HighEntropy]);
+        // Checksums, hashes and ordinary code are not secrets
+        assert!(kinds(
+            "checksum = \"9a6f6a9f7b3f5d1e2c4b8a0e6d2f1c3b5a7e9d0c2b4f6a8e0d2c4b6a8f0e2d4c\""
+        )
+        .is_empty());
+        assert!(kinds("let configuration_manager_factory = build();").is_empty());
+        assert!(kinds("url = \"https://example.com/some/long/path/here\"").is_empty());
+        assert!(kinds(NPM_INTEGRITY_LINE).is_empty());
+    }
+
+    #[test]
+    fn test_scan_diff_reports_path_and_line() {
+        let diff = format!(
+            "diff --git a/src/config.rs b/src/config.rs\n\
+             --- a/src/config.rs\n\
+             +++ b/src/config.rs\n\
+             @@ -10,3 +10,4 @@ fn config() {{\n \
+             let region = \"us-east-1\";\n\
+             -let old = \"{old}\";\n\
+             +let key = \"{aws}\";\n \
+             let retries = 3;\n",
+            old = GITHUB,
+            aws = AWS
+        );
+
+        let findings = scan_diff(&diff);
+        assert_eq!(
+            findings,
+            vec![Finding {
+                kind: SecretKind::AwsAccessKey,
+                path: "src/config.rs".to_string(),
+                line: 11,
+                secret: AWS.to_string(),
+                allowed_inline: false,
+            }]
+        );
+        assert_eq!(findings[0].masked(), "AKIA…");
+        assert_eq!(findings[0].fingerprint().len(), 16);
+
+        // Same secret further down the file, same fingerprint
+        let moved = scan_diff(&diff.replace("@@ -10,3 +10,4 @@", "@@ -90,3 +90,4 @@"));
+        assert_eq!(moved[0].line, 91);
+        assert_eq!(moved[0].fingerprint(), findings[0].fingerprint());
+
+        let marked = diff.replace(
+            &format!("+let key = \"{}\";", AWS),
+            &format!("+let key = \"{}\"; // noob-commit:allow", AWS),
+        );
+        assert!(scan_diff(&marked)[0].allowed_inline);
+    }
+
+    #[test]
+    fn test_redact_diff_uses_stable_placeholders() {
+        let diff = format!(
+            "diff --git a/src/config.rs b/src/config.rs\n\
+             --- a/src/config.rs\n\
+             +++ b/src/config.rs\n\
+             @@ -1,2 +1,3 @@\n\
+             -let key = \"{aws}\";\n\
+             +const KEY: &str = \"{aws}\";\n\
+             +const TOKEN: &str = \"{github}\";\n \
+             let retries = 3;\n",
+            aws = AWS,
+            github = GITHUB
+        );
+
+        let mut redactor = Redactor::new(&[]).unwrap();
+        let redacted = redactor.redact_diff(&diff);
+        assert_eq!(
+            redacted,
+            "diff --git a/src/config.rs b/src/config.rs\n\
+             --- a/src/config.rs\n\
+             +++ b/src/config.rs\n\
+             @@ -1,2 +1,3 @@\n\
+             -let key = \"<REDACTED:aws_key#1>\";\n\
+             +const KEY: &str = \"<REDACTED:aws_key#1>\";\n\
+             +const TOKEN: &str = \"<REDACTED:github_token#1>\";\n \
+             let retries = 3;\n"
+        );
+        assert_eq!(redactor.redacted(), 2);
+    }
+
+    #[test]
+    fn test_redact_custom_patterns_and_private_key_blocks() {
+        let diff = format!(
+            "diff --git a/deploy.sh b/deploy.sh\n\
+             --- a/deploy.sh\n\
+             +++ b/deploy.sh\n\
+             @@ -0,0 +1,6 @@\n\
+             +ssh deploy@db-prod-17.corp.internal\n\
+             +PASSWORD='hunter2'\n\
+             +cat > id <<EOF\n\
+             +{}\n\
+             +{}\n\
+             +{}\n",
+            PRIVATE_KEY_BEGIN, PRIVATE_KEY_BODY, PRIVATE_KEY_END
+        );
+        let patterns = vec![
+            r"[a-z0-9-]+\.corp\.internal".to_string(),
+            r"PASSWORD='([^']+)'".to_string(),
+        ];
+
+        let redacted = Redactor::new(&patterns).unwrap().redact_diff(&diff);
+        assert!(redacted.ends_with(
+            "@@ -0,0 +1,6 @@\n\
+             +ssh deploy@<REDACTED:custom#1>\n\
+             +PASSWORD='<REDACTED:custom#2>'\n\
+             +cat > id <<EOF\n\
+             +<REDACTED:private_key#1>\n\
+             +<REDACTED:private_key#1>\n\
+             +<REDACTED:private_key#1>\n"
+        ));
+
+        assert!(Redactor::new(&["(unclosed".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Map-reduce for diffs too big to send in one go.
+//!
+//! The diff is cut into chunks that each fit the input budget, every chunk is
+//! summarized on its own (concurrently), and the final [`CommitAdvice`] is
+//! written from the summaries.
+
+use crate::budget::{char_count, count_tokens, parse_diff, DiffBudget};
+use crate::provider::{CommitGenerator, DiffContext, ProviderError};
+use crate::CommitAdvice;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use log::info;
+use std::fmt::Write;
+
+/// Chunks summarized at the same time.
+const CONCURRENCY: usize = 4;
+
+/// Past this many chunks the rest is only listed by name, so a runaway diff
+/// can't turn into hundreds of API calls.
+pub const MAX_CHUNKS: usize = 24;
+
+/// Output budget for a single chunk summary.
+const SUMMARY_MAX_TOKENS: u16 = 500;
+
+const CHUNK_PROMPT: &str = "You are summarizing one part of a git diff that is too big to read at once. Put a one-line summary of this part in 'commit.title' and the important changes as short bullet points in 'commit.description'. Mention file names. If you find any API keys, say 'WARNING!!! API_KEY DETECTED' in 'message', otherwise leave 'message' empty.";
+
+/// When to summarize the diff in parts instead of trimming it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MapReduceMode {
+    /// Only when the diff doesn't fit the model's context window.
+    Auto,
+    /// Always summarize in parts.
+    Always,
+    /// Never, trim the diff to the budget instead.
+    Never,
+}
+
+/// A piece of the diff small enough for one request.
+#[derive(Debug, Clone, Default)]
+pub struct DiffChunk {
+    pub text: String,
+    pub paths: Vec<String>,
+    pub tokens: usize,
+}
+
+/// Cut `diff` into chunks that each fit `budget` (one file per chunk when
+/// it's unlimited), keeping files together when they fit and hunks whole
+/// otherwise.
+pub fn split_into_chunks(diff: &str, budget: &DiffBudget) -> Vec<DiffChunk> {
+    let unlimited = budget.max_tokens == 0 && budget.max_chars == 0;
+    let fits = |chunk: &DiffChunk, tokens: usize, text: &str| {
+        let chars = char_count(budget.max_chars, &chunk.text) + char_count(budget.max_chars, text);
+        budget.fits(chunk.tokens + tokens, chars)
+    };
+    let mut chunks: Vec<DiffChunk> = Vec::new();
+    let mut current = DiffChunk::default();
+
+    let mut flush = |current: &mut DiffChunk| {
+        if !current.text.is_empty() {
+            chunks.push(std::mem::take(current));
+        }
+    };
+
+    for file in parse_diff(diff) {
+        let file_text: String = std::iter::once(file.header.as_str())
+            .chain(file.hunks.iter().map(String::as_str))
+            .collect();
+        let file_tokens = count_tokens(&file_text);
+
+        if unlimited || fits(&DiffChunk::default(), file_tokens, &file_text) {
+            if unlimited || !fits(&current, file_tokens, &file_text) {
+                flush(&mut current);
+            }
+            current.text.push_str(&file_text);
+            current.paths.push(file.path.clone());
+            current.tokens += file_tokens;
+            continue;
+        }
+
+        // Too big for a chunk of its own: spread its hunks over several
+        // chunks, repeating the header so each one makes sense on its own
+        flush(&mut current);
+        let header_only = DiffChunk {
+            text: file.header.clone(),
+            paths: vec![file.path.clone()],
+            tokens: count_tokens(&file.header),
+        };
+        for hunk in &file.hunks {
+            let hunk_tokens = count_tokens(hunk);
+            if current.text.is_empty() || !fits(&current, hunk_tokens, hunk) {
+                flush(&mut current);
+                current = header_only.clone();
+            }
+            if fits(&header_only, hunk_tokens, hunk) {
+                current.text.push_str(hunk);
+                current.tokens += hunk_tokens;
+            } else {
+                // A single monster hunk: let the budgeter leave it out properly
+                let alone = format!("{}{}", file.header, hunk);
+                let budgeted = budget.apply(&alone);
+                current.text = budgeted.text;
+                current.tokens = budgeted.tokens;
+            }
+        }
+        flush(&mut current);
+    }
+    flush(&mut current);
+
+    chunks
+}
+
+/// Summarize every chunk concurrently, then ask for the final commit advice.
+pub async fn map_reduce(
+    generator: &dyn CommitGenerator,
+    system_prompt: &str,
+    chunks: &[DiffChunk],
+    max_tokens: u16,
+) -> Result<CommitAdvice, ProviderError> {
+    let (included, skipped) = chunks.split_at(chunks.len().min(MAX_CHUNKS));
+    let total = included.len();
+    info!("🧩 Diff is too big, summarizing it in {} parts...", total);
+
+    let summaries: Vec<CommitAdvice> = stream::iter(included.iter().enumerate())
+        .map(|(i, chunk)| async move {
+            let ctx = DiffContext::new(
+                format!("{} This is part {} of {}.", CHUNK_PROMPT, i + 1, total),
+                chunk.text.clone(),
+                SUMMARY_MAX_TOKENS.min(max_tokens),
+            );
+            let summary = generator.generate(&ctx).await;
+            info!("🧩 Summarized part {}/{}", i + 1, total);
+            summary
+        })
+        .buffered(CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut combined = String::new();
+    for (i, (chunk, summary)) in included.iter().zip(&summaries).enumerate() {
+        let _ = writeln!(
+            combined,
+            "### Part {} ({})\n{}\n{}\n",
+            i + 1,
+            chunk.paths.join(", "),
+            summary.commit,
+            summary.message
+        );
+    }
+    if !skipped.is_empty() {
+        let paths: Vec<&str> = skipped
+            .iter()
+            .flat_map(|c| c.paths.iter().map(String::as_str))
+            .collect();
+        let _ = writeln!(
+            combined,
+            "These files also changed but were not summarized: {}",
+            paths.join(", ")
+        );
+    }
+
+    let ctx = DiffContext::new(system_prompt.to_string(), combined, max_tokens).with_intro(
+        "The git diff was too big to send at once. Here are summaries of each part of it:",
+    );
+    generator.generate(&ctx).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Commit;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    fn file(path: &str, hunks: &[&str]) -> String {
+        let mut out = format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n");
+        for body in hunks {
+            out.push_str(&format!("@@ -1 +1 @@\n{}\n", body));
+        }
+        out
+    }
+
+    #[test]
+    fn test_small_files_share_a_chunk() {
+        let diff = file("a.rs", &["+a"]) + &file("b.rs", &["+b"]);
+        let chunks = split_into_chunks(&diff, &DiffBudget::new(1000, 0));
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].paths, vec!["a.rs", "b.rs"]);
+        assert_eq!(chunks[0].text, diff);
+    }
+
+    #[test]
+    fn test_big_file_split_by_hunks_with_header() {
+        let hunk = "+let x = 1;\n".repeat(40);
+        let diff = file("big.rs", &[&hunk, &hunk, &hunk]);
+        let per_hunk = count_tokens(&format!("@@ -1 +1 @@\n{}\n", hunk));
+        let chunks = split_into_chunks(&diff, &DiffBudget::new(per_hunk + 40, 0));
+
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            assert!(chunk.text.starts_with("diff --git a/big.rs b/big.rs\n"));
+            assert_eq!(chunk.text.matches("@@ -1 +1 @@").count(), 1);
+            assert!(chunk.tokens <= per_hunk + 40);
+        }
+    }
+
+    #[test]
+    fn test_zero_budget_is_one_file_per_chunk() {
+        let diff = file("a.rs", &["+a"]) + &file("b.rs", &["+b"]);
+        let chunks = split_into_chunks(&diff, &DiffBudget::new(0, 0));
+        assert_eq!(chunks.len(), 2);
+    }
+
+    #[test]
+    fn test_character_budget_splits_too() {
+        let hunk = "+let x = 1;\n".repeat(40);
+        let diff = file("a.rs", &[&hunk]) + &file("b.rs", &[&hunk]) + &file("c.rs", &[&hunk]);
+        let file_chars = diff.len() / 3;
+
+        // Plenty of tokens, but only room for one file's characters
+        let chunks = split_into_chunks(&diff, &DiffBudget::new(100_000, file_chars + 10));
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            assert!(chunk.text.len() <= file_chars + 10);
+        }
+
+        // Both limits count: the tighter one wins
+        let chunks = split_into_chunks(&diff, &DiffBudget::new(100_000, 2 * file_chars));
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].paths, vec!["a.rs", "b.rs"]);
+    }
+
+    /// Records every prompt and answers with the part it was asked about.
+    struct Recorder {
+        prompts: Mutex<Vec<DiffContext>>,
+    }
+
+    #[async_trait]
+    impl CommitGenerator for Recorder {
+        fn name(&self) -> String {
+            "recorder".to_string()
+        }
+
+        async fn generate(&self, ctx: &DiffContext) -> Result<CommitAdvice, ProviderError> {
+            self.prompts.lock().unwrap().push(ctx.clone());
+            let title = if ctx.user_prompt().contains("summaries") {
+                "Final"
+            } else if ctx.diff.contains("a.rs") {
+                "Touch a"
+            } else {
+                "Touch b"
+            };
+            Ok(CommitAdvice::new(
+                String::new(),
+                Commit::new(title.to_string(), "details".to_string()),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_map_reduce_combines_summaries() {
+        let recorder = Recorder {
+            prompts: Mutex::new(Vec::new()),
+        };
+        let diff = file("a.rs", &["+a"]) + &file("b.rs", &["+b"]);
+        let chunks = split_into_chunks(&diff, &DiffBudget::new(0, 0));
+
+        let advice = map_reduce(&recorder, "write a commit", &chunks, 2000)
+            .await
+            .unwrap();
+
+        let prompts = recorder.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 3);
+        assert!(prompts[0].system_prompt.contains("part 1 of 2"));
+        assert_eq!(prompts[0].max_tokens, SUMMARY_MAX_TOKENS);
+
+        let last = prompts.last().unwrap();
+        assert_eq!(last.system_prompt, "write a commit");
+        assert!(last.user_prompt().contains("summaries of each part"));
+        assert!(last.diff.contains("### Part 1 (a.rs)\nTouch a"));
+        assert!(last.diff.contains("### Part 2 (b.rs)\nTouch b"));
+        assert_eq!(advice.commit.title, "Final");
+    }
+}
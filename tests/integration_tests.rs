@@ -1,9 +1,10 @@
+
 use std::process::Command;
 
 #[test]
 fn test_help_command() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "--help"])
+        .args(["run", "--", "--help"])
         .output()
         .expect("Failed to execute help command");
 
@@ -21,7 +22,7 @@ fn test_help_command() {
 #[test]
 fn test_version_command() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "--version"])
+        .args(["run", "--", "--version"])
         .output()
         .expect("Failed to execute version command");
 
@@ -33,7 +34,7 @@ fn test_version_command() {
 #[test]
 fn test_dry_run_without_openai_key() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "--dry-run"])
+        .args(["run", "--", "--dry-run"])
         .env_remove("OPENAI_API_KEY")
         .output()
         .expect("Failed to execute dry-run command");
@@ -46,6 +47,20 @@ fn test_dry_run_without_openai_key() {
     assert!(stderr.contains("platform.openai.com"));
 }
 
+#[test]
+fn test_dry_run_without_anthropic_key() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "--dry-run", "--provider", "anthropic"])
+        .env_remove("ANTHROPIC_API_KEY")
+        .output()
+        .expect("Failed to execute dry-run command");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(stderr.contains("forgot to set ANTHROPIC_API_KEY"));
+    assert!(stderr.contains("console.anthropic.com"));
+}
+
 #[test]
 fn test_non_git_directory() {
     // Create a temporary directory outside of any git repo
@@ -65,7 +80,7 @@ fn test_non_git_directory() {
         .join("noob-commit");
 
     let output = Command::new(&binary_path)
-        .args(&["--dry-run"])
+        .args(["--dry-run"])
         .current_dir(&temp_dir)
         .env("OPENAI_API_KEY", "test-key")
         .output()
@@ -114,6 +129,7 @@ mod cli_tests {
         // Test all our custom flags are present
         let expected_flags = [
             "-d, --dry-run",
+            "--staged-only",
             "-r, --review",
             "-f, --force",
             "-e, --ok-to-send-env",
@@ -121,6 +137,7 @@ mod cli_tests {
             "-t, --max-tokens",
             "-i, --max-input-chars",
             "-m, --model",
+            "-P, --provider",
             "-s, --setup-alias",
             "-M, --yes-to-modules",
             "-c, --yes-to-crap",
@@ -180,20 +197,20 @@ fn test_max_input_chars_truncation() {
     
     // Initialize git repo
     Command::new("git")
-        .args(&["init"])
+        .args(["init"])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to init git repo");
     
     // Configure git
     Command::new("git")
-        .args(&["config", "user.email", "test@example.com"])
+        .args(["config", "user.email", "test@example.com"])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to set git email");
     
     Command::new("git")
-        .args(&["config", "user.name", "Test User"])
+        .args(["config", "user.name", "Test User"])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to set git name");
@@ -206,13 +223,13 @@ fn test_max_input_chars_truncation() {
     
     // Add and commit initial version
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to add files");
     
     Command::new("git")
-        .args(&["commit", "-m", "Initial commit"])
+        .args(["commit", "-m", "Initial commit"])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to commit");
@@ -223,14 +240,14 @@ fn test_max_input_chars_truncation() {
     
     // Stage the changes
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to add files");
     
     // Get the diff size
     let diff_output = Command::new("git")
-        .args(&["diff", "--staged"])
+        .args(["diff", "--staged"])
         .current_dir(&temp_dir)
         .output()
         .expect("Failed to get diff");
@@ -247,7 +264,7 @@ fn test_max_input_chars_truncation() {
     
     // We can't test the actual API call, but we can verify the flag is accepted
     let output = Command::new(&binary_path)
-        .args(&["--dry-run", "--max-input-chars", "0"])
+        .args(["--dry-run", "--max-input-chars", "0"])
         .current_dir(&temp_dir)
         .env("OPENAI_API_KEY", "test-key")
         .output()
@@ -259,7 +276,7 @@ fn test_max_input_chars_truncation() {
     
     // Test with small max-input-chars to ensure truncation would happen
     let output = Command::new(&binary_path)
-        .args(&["--dry-run", "--max-input-chars", "100"])
+        .args(["--dry-run", "--max-input-chars", "100"])
         .current_dir(&temp_dir)
         .env("OPENAI_API_KEY", "test-key")
         .output()
@@ -272,3 +289,1400 @@ fn test_max_input_chars_truncation() {
     // Cleanup
     fs::remove_dir_all(&temp_dir).ok();
 }
+
+#[allow(dead_code)]
+#[path = "fixtures/fake_keys.rs"]
+mod fake_keys;
+
+/// Create an empty git repo with a committer identity in a fresh temp dir.
+fn init_temp_repo(name: &str) -> std::path::PathBuf {
+    let temp_dir =
+        std::env::temp_dir().join(format!("noob-commit-{}-{}", name, std::process::id()));
+    std::fs::remove_dir_all(&temp_dir).ok();
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    git(&temp_dir, &["init", "-q"]);
+    git(&temp_dir, &["config", "user.email", "test@example.com"]);
+    git(&temp_dir, &["config", "user.name", "Test User"]);
+
+    temp_dir
+}
+
+/// Run git in `dir` and return its stdout, failing the test if git fails.
+fn git(dir: &std::path::Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git");
+    assert!(
+        output.status.success(),
+        "git {}: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// noob-commit, run in `dir` without the developer's global config.
+fn noob_commit(dir: &std::path::Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_noob-commit"));
+    command
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("no-global-config"));
+    command
+}
+
+#[test]
+fn test_local_provider_down_is_friendly() {
+    let temp_dir = init_temp_repo("ollama-down");
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+    // Grab a free port and release it so nothing is listening there
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base"])
+        .arg(format!("http://127.0.0.1:{}", port))
+        .env_remove("OPENAI_API_KEY")
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(!stderr.contains("panicked"), "got: {}", stderr);
+    assert!(stderr.contains("Couldn't reach the AI"), "got: {}", stderr);
+    assert!(stderr.contains("ollama serve"), "got: {}", stderr);
+    assert_eq!(output.status.code(), Some(3));
+
+    // The failed run must not leave main.rs staged behind our back
+    let staged = git(&temp_dir, &["diff", "--cached", "--name-only"]);
+    assert!(staged.is_empty(), "index was not restored");
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+/// Serve a single Ollama `/api/chat` request with a canned commit and hand
+/// back the raw request body.
+fn mock_ollama_once() -> (String, std::thread::JoinHandle<String>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+
+        let advice =
+            r#"{"message":"Nice","commit":{"title":"Update main","description":"Tweaks"}}"#;
+        let response = serde_json::json!({ "message": { "content": advice } }).to_string();
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.len(),
+            response
+        )
+        .unwrap();
+
+        String::from_utf8(body).unwrap()
+    });
+
+    (url, handle)
+}
+
+#[test]
+fn test_filtered_paths_never_reach_the_ai() {
+    let temp_dir = init_temp_repo("staged-only-diff");
+
+    // A teammate committed .env long ago, now it holds a real secret
+    std::fs::write(temp_dir.join(".env"), "TOKEN=placeholder\n").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    std::fs::write(temp_dir.join(".env"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+    std::fs::write(
+        temp_dir.join("main.rs"),
+        "fn main() { println!(\"hi\"); }\n",
+    )
+    .unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(
+        body.contains("println!"),
+        "main.rs change missing: {}",
+        body
+    );
+    assert!(
+        !body.contains("SUPER_SECRET_VALUE"),
+        "secret leaked: {}",
+        body
+    );
+    assert!(!body.contains(".env"), ".env leaked: {}", body);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_diff_over_the_char_budget_is_trimmed_not_map_reduced() {
+    let temp_dir = init_temp_repo("trim-not-map-reduce");
+    for i in 0..8 {
+        let body: String = (0..100)
+            .map(|line| format!("let value_{}_{} = {};\n", i, line, line))
+            .collect();
+        std::fs::write(temp_dir.join(format!("file_{}.rs", i)), body).unwrap();
+    }
+
+    // Way over -i but nowhere near the context window: trim it. And a diff
+    // that fits in one chunk is one plain request even when map-reduce is
+    // forced. The mock only answers once, so any summarizing would fail.
+    for (mode, input_chars) in [("auto", "2000"), ("always", "0")] {
+        let (url, server) = mock_ollama_once();
+        let output = noob_commit(&temp_dir)
+            .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+            .args(["--map-reduce", mode, "-i", input_chars])
+            .output()
+            .expect("Failed to execute command");
+        let body = server.join().unwrap();
+
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "{}: {}", mode, stderr);
+        assert!(!stderr.contains("summarizing it in"), "got: {}", stderr);
+        if mode == "auto" {
+            assert!(stderr.contains("Trimming git diff"), "got: {}", stderr);
+            assert!(body.len() < 4000, "{} bytes sent", body.len());
+        } else {
+            assert!(body.contains("let value_7_99 = 99;"), "got: {}", body);
+        }
+    }
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_first_commit_in_new_repo() {
+    let temp_dir = init_temp_repo("first-commit");
+    std::fs::create_dir_all(temp_dir.join("src")).unwrap();
+    std::fs::write(temp_dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+    std::fs::write(temp_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    std::fs::write(temp_dir.join(".env"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(body.contains("very first commit"), "got: {}", body);
+    assert!(body.contains("- src/ (1 files)"), "got: {}", body);
+    assert!(body.contains("fn main() {}"), "diff missing: {}", body);
+    // With no HEAD to reset to, .env still has to come out of the index
+    assert!(
+        !body.contains("SUPER_SECRET_VALUE"),
+        "secret leaked: {}",
+        body
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_answering_no_restores_the_index() {
+    let temp_dir = init_temp_repo("answer-no");
+
+    std::fs::write(temp_dir.join("a.rs"), "fn a() {}\n").unwrap();
+    std::fs::write(temp_dir.join("b.rs"), "fn b() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+
+    // Carefully stage only a.rs, leave b.rs for another commit
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+    std::fs::write(temp_dir.join("b.rs"), "fn b() { 2; }\n").unwrap();
+    git(&temp_dir, &["add", "a.rs"]);
+
+    let (url, server) = mock_ollama_once();
+    let mut child = noob_commit(&temp_dir)
+        .args(["--no-push", "--provider", "ollama", "--api-base", &url])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+    {
+        use std::io::Write;
+        child.stdin.take().unwrap().write_all(b"n\n").unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "got: {}", stderr);
+    assert!(stderr.contains("Chickened out"), "got: {}", stderr);
+
+    let staged = git(&temp_dir, &["diff", "--cached", "--name-only"]);
+    assert_eq!(staged, "a.rs\n");
+    let log = git(&temp_dir, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(log.trim(), "1");
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_failed_run_restores_the_index_byte_for_byte() {
+    let temp_dir = init_temp_repo("restore-exact");
+
+    std::fs::write(temp_dir.join("a.rs"), "fn a() {}\n").unwrap();
+    std::fs::write(temp_dir.join("b.rs"), "fn b() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+
+    // Things a tree can't hold: an intent-to-add entry and index flags
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+    std::fs::write(temp_dir.join("c.rs"), "fn c() {}\n").unwrap();
+    git(&temp_dir, &["add", "-N", "c.rs"]);
+    git(&temp_dir, &["update-index", "--skip-worktree", "b.rs"]);
+    let index_before = std::fs::read(temp_dir.join(".git/index")).unwrap();
+
+    let output = noob_commit(&temp_dir)
+        .args([
+            "--force",
+            "--no-push",
+            "--provider",
+            "ollama",
+            "--api-base",
+            "http://127.0.0.1:9",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(3), "got: {}", stderr);
+
+    let index_after = std::fs::read(temp_dir.join(".git/index")).unwrap();
+    assert!(index_before == index_after, "index not restored exactly");
+    assert_eq!(git(&temp_dir, &["ls-files", "-t", "b.rs"]), "S b.rs\n");
+    assert!(!temp_dir.join(".git/index.noob-commit").exists());
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_conflicted_merge_is_restored_too() {
+    let temp_dir = init_temp_repo("restore-conflict");
+
+    std::fs::write(temp_dir.join("a.rs"), "fn a() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    git(&temp_dir, &["checkout", "-q", "-b", "other"]);
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 2; }\n").unwrap();
+    git(&temp_dir, &["commit", "-q", "-am", "two"]);
+    git(&temp_dir, &["checkout", "-q", "-"]);
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+    git(&temp_dir, &["commit", "-q", "-am", "one"]);
+    let merge = std::process::Command::new("git")
+        .args(["merge", "-q", "other"])
+        .current_dir(&temp_dir)
+        .output()
+        .unwrap();
+    assert!(!merge.status.success(), "expected a conflict");
+    let index_before = std::fs::read(temp_dir.join(".git/index")).unwrap();
+
+    let output = noob_commit(&temp_dir)
+        .args([
+            "--force",
+            "--no-push",
+            "--provider",
+            "ollama",
+            "--api-base",
+            "http://127.0.0.1:9",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_ne!(output.status.code(), Some(0), "got: {}", stderr);
+
+    let index_after = std::fs::read(temp_dir.join(".git/index")).unwrap();
+    assert!(index_before == index_after, "conflicts were lost");
+    assert_eq!(
+        git(&temp_dir, &["diff", "--name-only", "--diff-filter=U"]),
+        "a.rs\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_dry_run_leaves_the_index_untouched() {
+    let temp_dir = init_temp_repo("dry-run-index");
+
+    std::fs::write(temp_dir.join("a.rs"), "fn a() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+    std::fs::write(temp_dir.join("new.rs"), "fn new() {}\n").unwrap();
+    std::fs::write(temp_dir.join(".env"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+
+    let index_before = std::fs::read(temp_dir.join(".git/index")).unwrap();
+    let head_before = git(&temp_dir, &["rev-parse", "HEAD"]);
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    // The AI still saw the would-be commit...
+    assert!(body.contains("fn new() {}"), "got: {}", body);
+    assert!(
+        !body.contains("SUPER_SECRET_VALUE"),
+        "secret leaked: {}",
+        body
+    );
+    // ...but nothing on disk changed
+    let index_after = std::fs::read(temp_dir.join(".git/index")).unwrap();
+    assert!(index_before == index_after, "dry run modified the index");
+    assert_eq!(git(&temp_dir, &["rev-parse", "HEAD"]), head_before);
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join("a.rs")).unwrap(),
+        "fn a() { 1; }\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_staged_only_uses_the_index_as_is() {
+    let temp_dir = init_temp_repo("staged-only");
+
+    std::fs::write(temp_dir.join("a.rs"), "fn a() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+    std::fs::write(temp_dir.join("wip.rs"), "fn wip() {}\n").unwrap();
+    git(&temp_dir, &["add", "a.rs"]);
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--staged-only", "--provider", "ollama"])
+        .args(["--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(body.contains("fn a() { 1; }"), "got: {}", body);
+    assert!(
+        !body.contains("wip.rs"),
+        "unstaged file was added: {}",
+        body
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_staged_only_can_be_the_repo_default() {
+    let temp_dir = init_temp_repo("staged-only-config");
+
+    std::fs::write(temp_dir.join("a.rs"), "fn a() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    std::fs::write(temp_dir.join(".noob-commit.toml"), "staged_only = true\n").unwrap();
+    std::fs::write(temp_dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+    std::fs::write(temp_dir.join("wip.rs"), "fn wip() {}\n").unwrap();
+    git(&temp_dir, &["add", "a.rs"]);
+
+    let run = |args: &[&str]| {
+        let (url, server) = mock_ollama_once();
+        let output = noob_commit(&temp_dir)
+            .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "got: {}", stderr);
+        server.join().unwrap()
+    };
+
+    let body = run(&[]);
+    assert!(body.contains("fn a() { 1; }"), "got: {}", body);
+    assert!(
+        !body.contains("wip.rs"),
+        "unstaged file was added: {}",
+        body
+    );
+
+    // The flag still wins
+    let body = run(&["--staged-only=false"]);
+    assert!(body.contains("wip.rs"), "got: {}", body);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_staged_only_blocks_staged_secrets() {
+    let temp_dir = init_temp_repo("staged-only-secret");
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    std::fs::write(temp_dir.join(".env"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+
+    // Nothing listens here, the run must stop before asking the AI
+    let output = noob_commit(&temp_dir)
+        .args(["--staged-only", "--provider", "ollama"])
+        .args(["--api-base", "http://127.0.0.1:9"])
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "got: {}", stderr);
+    assert!(
+        stderr.contains("You staged a security file: .env"),
+        "got: {}",
+        stderr
+    );
+    assert!(!stderr.contains("Couldn't reach the AI"), "got: {}", stderr);
+
+    // The hand-crafted index is left alone
+    let staged = git(&temp_dir, &["diff", "--cached", "--name-only"]);
+    assert_eq!(staged, ".env\nmain.rs\n");
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+/// A committed repo with an uncommitted change at the root and in `src/`.
+fn nested_repo(name: &str) -> std::path::PathBuf {
+    let temp_dir = init_temp_repo(name);
+    std::fs::create_dir_all(temp_dir.join("src/deep")).unwrap();
+    std::fs::write(temp_dir.join("README.md"), "# demo\n").unwrap();
+    std::fs::write(temp_dir.join("src/deep/lib.rs"), "fn lib() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    std::fs::write(temp_dir.join("README.md"), "# demo\nROOT_CHANGE\n").unwrap();
+    std::fs::write(
+        temp_dir.join("src/deep/lib.rs"),
+        "fn lib() { NESTED_CHANGE; }\n",
+    )
+    .unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_runs_on_the_whole_repo_from_a_subdirectory() {
+    let temp_dir = nested_repo("subdir-root");
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir.join("src/deep"))
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(body.contains("ROOT_CHANGE"), "got: {}", body);
+    assert!(body.contains("NESTED_CHANGE"), "got: {}", body);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_path_scope_is_relative_to_cwd() {
+    let temp_dir = nested_repo("subdir-scope");
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir.join("src"))
+        .args(["--dry-run", "--path", ".", "--provider", "ollama"])
+        .args(["--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(body.contains("NESTED_CHANGE"), "got: {}", body);
+    assert!(!body.contains("ROOT_CHANGE"), "out of scope: {}", body);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_path_scope_still_filters_what_is_already_staged() {
+    let temp_dir = nested_repo("subdir-scope-staged");
+    std::fs::write(temp_dir.join(".env"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+    git(&temp_dir, &["add", ".env"]);
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir.join("src"))
+        .args([
+            "--force",
+            "--no-push",
+            "--path",
+            ".",
+            "--provider",
+            "ollama",
+        ])
+        .args(["--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(
+        stderr.contains("Protecting security file"),
+        "got: {}",
+        stderr
+    );
+    assert!(
+        !body.contains("SUPER_SECRET_VALUE"),
+        "secret leaked: {}",
+        body
+    );
+    assert_eq!(
+        git(&temp_dir, &["show", "--name-only", "--format=", "HEAD"]),
+        "src/deep/lib.rs\n"
+    );
+    // Out of scope and not staged: left alone
+    assert_eq!(
+        git(&temp_dir, &["status", "--porcelain"]),
+        " M README.md\n?? .env\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_env_file_found_at_repo_root_from_subdirectory() {
+    let temp_dir = nested_repo("subdir-env");
+    std::fs::write(temp_dir.join(".env"), "ANTHROPIC_API_KEY=sk-ant-test\n").unwrap();
+
+    // The key is found, so the run gets as far as the (unreachable) API
+    let output = noob_commit(&temp_dir.join("src/deep"))
+        .args(["--dry-run", "--provider", "anthropic"])
+        .args(["--api-base", "http://127.0.0.1:9", "--retries", "0"])
+        .env_remove("ANTHROPIC_API_KEY")
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.contains("forgot to set"), "got: {}", stderr);
+    assert!(stderr.contains("Couldn't reach the AI"), "got: {}", stderr);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_only_changed_paths_are_filtered() {
+    let temp_dir = init_temp_repo("changed-only");
+    std::fs::create_dir_all(temp_dir.join("build")).unwrap();
+    std::fs::write(temp_dir.join("build/keep.txt"), "committed on purpose\n").unwrap();
+    git(&temp_dir, &["add", "-f", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+
+    std::fs::write(temp_dir.join("my notes \"v2\".md"), "ODD_NAME_CHANGE\n").unwrap();
+    std::fs::create_dir_all(temp_dir.join("node_modules/left pad")).unwrap();
+    std::fs::write(
+        temp_dir.join("node_modules/left pad/index.js"),
+        "MODULE_CHANGE\n",
+    )
+    .unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    // Untouched tracked files are none of our business
+    assert!(!stderr.contains("build/keep.txt"), "got: {}", stderr);
+    assert!(
+        stderr.contains("node_modules/left pad/index.js"),
+        "got: {}",
+        stderr
+    );
+    assert!(body.contains("ODD_NAME_CHANGE"), "got: {}", body);
+    assert!(!body.contains("MODULE_CHANGE"), "module leaked: {}", body);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_unstaging_a_filtered_rename_keeps_its_source() {
+    let temp_dir = init_temp_repo("rename-filtered");
+    std::fs::write(temp_dir.join("keep.txt"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "init"]);
+    git(&temp_dir, &["mv", "keep.txt", ".env"]);
+    std::fs::write(temp_dir.join("main.rs"), "fn main() { 1; }\n").unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args([
+            "--force",
+            "--no-push",
+            "--provider",
+            "ollama",
+            "--api-base",
+            &url,
+        ])
+        .output()
+        .expect("Failed to execute command");
+    server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    // Only main.rs changed, keep.txt wasn't deleted behind the noob's back
+    assert_eq!(
+        git(&temp_dir, &["show", "--name-status", "--format=", "HEAD"]),
+        "M\tmain.rs\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_filtered_files_are_never_hashed_into_objects() {
+    let temp_dir = init_temp_repo("never-staged");
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    std::fs::create_dir_all(temp_dir.join("node_modules/pkg")).unwrap();
+    let module = "module.exports = 'NEVER_HASHED';\n";
+    std::fs::write(temp_dir.join("node_modules/pkg/index.js"), module).unwrap();
+    std::fs::write(temp_dir.join(".env"), "TOKEN=SUPER_SECRET_VALUE\n").unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(body.contains("fn main() {}"), "got: {}", body);
+
+    // Neither file was ever added, so git has no blob for them
+    for path in ["node_modules/pkg/index.js", ".env"] {
+        let hash = git(&temp_dir, &["hash-object", path]);
+        let exists = Command::new("git")
+            .args(["cat-file", "-e", hash.trim()])
+            .current_dir(&temp_dir)
+            .status()
+            .unwrap();
+        assert!(!exists.success(), "{} was hashed into .git/objects", path);
+    }
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_repo_config_allows_vendored_code() {
+    let temp_dir = init_temp_repo("filter-config");
+    std::fs::create_dir_all(temp_dir.join("vendor/lib")).unwrap();
+    std::fs::write(temp_dir.join("vendor/lib/dep.go"), "package VENDORED\n").unwrap();
+    std::fs::write(temp_dir.join("secret.pem"), "PEM_CONTENT\n").unwrap();
+    std::fs::write(
+        temp_dir.join(".noob-commit.toml"),
+        "[modules]\nallow = [\"vendor/\"]\n\n[security]\ndeny = [\"*.pem\"]\n",
+    )
+    .unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(body.contains("VENDORED"), "allowed path missing: {}", body);
+    assert!(
+        !body.contains("PEM_CONTENT"),
+        "denied path leaked: {}",
+        body
+    );
+    assert!(stderr.contains("security file"), "got: {}", stderr);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_broken_filter_config_is_friendly() {
+    let temp_dir = init_temp_repo("filter-config-broken");
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    std::fs::write(
+        temp_dir.join(".noob-commit.toml"),
+        "[crap]\ndeny = \"*.log\"\n",
+    )
+    .unwrap();
+
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama"])
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "got: {}", stderr);
+    assert!(stderr.contains(".noob-commit.toml"), "got: {}", stderr);
+    assert!(!stderr.contains("panicked"), "got: {}", stderr);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+fn explain(dir: &std::path::Path, args: &[&str]) -> String {
+    let output = noob_commit(dir)
+        .args(["filters", "explain"])
+        .args(args)
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "got: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_filters_explain() {
+    let temp_dir = init_temp_repo("filters-explain");
+    std::fs::create_dir_all(temp_dir.join("src")).unwrap();
+    std::fs::write(
+        temp_dir.join(".noob-commit.toml"),
+        "[modules]\nallow = [\"vendor/\"]\n",
+    )
+    .unwrap();
+
+    // Relative to where you are, like any other command
+    let stdout = explain(&temp_dir.join("src"), &["../node_modules/pad/index.js"]);
+    assert!(
+        stdout.contains("🔎 node_modules/pad/index.js"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("dependency/module folder"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("'node_modules/' from built-in defaults"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("--yes-to-modules"), "got: {}", stdout);
+
+    let stdout = explain(&temp_dir, &["vendor/dep.go"]);
+    assert!(stdout.contains("allowed by 'vendor/'"), "got: {}", stdout);
+    assert!(stdout.contains(".noob-commit.toml"), "got: {}", stdout);
+    assert!(stdout.contains("Not filtered"), "got: {}", stdout);
+
+    std::fs::write(temp_dir.join(".env"), "TOKEN=x\n").unwrap();
+    std::fs::write(temp_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    let stdout = explain(&temp_dir, &["--all"]);
+    assert!(
+        stdout.contains("🚫 .env  (security file: '.env'"),
+        "got: {}",
+        stdout
+    );
+    assert!(!stdout.contains("main.rs"), "got: {}", stdout);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_pasted_secret_blocks_commit_and_ai() {
+    let temp_dir = init_temp_repo("secret-scan");
+    let key = fake_keys::AWS;
+    std::fs::write(
+        temp_dir.join("config.rs"),
+        format!("// config\nconst KEY: &str = \"{}\";\n", key),
+    )
+    .unwrap();
+
+    // Nothing listens here, the run must stop before asking the AI
+    let output = noob_commit(&temp_dir)
+        .args(["--force", "--no-push", "--provider", "ollama"])
+        .args(["--api-base", "http://127.0.0.1:9"])
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4), "got: {}", stderr);
+    assert!(
+        stderr.contains("AWS access key in config.rs:2"),
+        "got: {}",
+        stderr
+    );
+    assert!(!stderr.contains(key), "printed the whole key: {}", stderr);
+    assert!(!stderr.contains("Couldn't reach the AI"), "got: {}", stderr);
+
+    let staged = git(&temp_dir, &["diff", "--cached", "--name-only"]);
+    assert!(staged.is_empty(), "index was not restored");
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_fake_secrets_are_redacted_from_the_prompt() {
+    let temp_dir = init_temp_repo("secret-redact");
+    let key = fake_keys::AWS;
+    std::fs::write(
+        temp_dir.join("fixtures.rs"),
+        format!(
+            "const KEY: &str = \"{}\";\nconst HOST: &str = \"db-prod-17\";\n",
+            key
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        temp_dir.join(".noob-commit.toml"),
+        "[redact]\npatterns = ['db-prod-[0-9]+']\n",
+    )
+    .unwrap();
+
+    // The fixtures get committed, but only placeholders go to the AI
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--ok-to-send-env", "--print-prompt"])
+        .args(["--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    let body = server.join().unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.success(),
+        "got: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("----- USER PROMPT -----"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("+const KEY: &str = \"<REDACTED:aws_key#1>\";"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("+const HOST: &str = \"<REDACTED:custom#1>\";"),
+        "got: {}",
+        stdout
+    );
+    for sent in [&stdout, &body] {
+        assert!(!sent.contains(key), "key left the machine: {}", sent);
+        assert!(
+            !sent.contains("db-prod-17"),
+            "host left the machine: {}",
+            sent
+        );
+    }
+    assert!(body.contains("<REDACTED:aws_key#1>"), "got: {}", body);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_doctor_untracks_committed_secrets_in_one_commit() {
+    let temp_dir = init_temp_repo("doctor-secrets");
+    std::fs::create_dir_all(temp_dir.join("node_modules/pad")).unwrap();
+    std::fs::write(
+        temp_dir.join("node_modules/pad/index.js"),
+        "module.exports = 1;\n",
+    )
+    .unwrap();
+    std::fs::write(temp_dir.join(".env"), "SECRET=hunter2\n").unwrap();
+    std::fs::write(temp_dir.join(".gitignore"), "target/").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    git(&temp_dir, &["add", "--force", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "Oops"]);
+
+    let doctor = |args: &[&str]| {
+        noob_commit(&temp_dir)
+            .args(["doctor", "secrets"])
+            .args(args)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let output = doctor(&["--dry-run"]);
+    assert!(output.status.success());
+    assert_eq!(
+        git(&temp_dir, &["ls-files"]).lines().count(),
+        4,
+        "dry run changed things"
+    );
+
+    let output = doctor(&["--force"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(
+        stderr.contains("Tracked security file ('.env'): .env"),
+        "got: {}",
+        stderr
+    );
+    assert!(stderr.contains("haven't pushed yet"), "got: {}", stderr);
+
+    assert_eq!(git(&temp_dir, &["ls-files"]), ".gitignore\nmain.rs\n");
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap(),
+        "target/\n\n# Added by noob-commit\n.env\nnode_modules/\n"
+    );
+    assert!(temp_dir.join(".env").exists(), "deleted the file itself");
+    assert_eq!(git(&temp_dir, &["rev-list", "--count", "HEAD"]).trim(), "2");
+    assert_eq!(
+        git(&temp_dir, &["log", "-1", "--format=%s"]).trim(),
+        "Stop tracking files that should be ignored"
+    );
+    assert_eq!(
+        git(&temp_dir, &["status", "--porcelain"]),
+        "",
+        "left changes behind"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_doctor_leaves_tracked_placeholders_alone() {
+    let temp_dir = init_temp_repo("doctor-gitkeep");
+    std::fs::create_dir_all(temp_dir.join("logs")).unwrap();
+    std::fs::write(temp_dir.join("logs/.gitkeep"), "").unwrap();
+    std::fs::write(temp_dir.join(".env"), "SECRET=hunter2\n").unwrap();
+    git(&temp_dir, &["add", "--force", "."]);
+    git(&temp_dir, &["commit", "-q", "-m", "Oops"]);
+
+    let output = noob_commit(&temp_dir)
+        .args(["doctor", "secrets", "--force"])
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(!stderr.contains("cache/build artifact"), "got: {}", stderr);
+
+    assert_eq!(git(&temp_dir, &["ls-files"]), ".gitignore\nlogs/.gitkeep\n");
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap(),
+        "# Added by noob-commit\n.env\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_history_is_not_scanned_without_a_remote() {
+    let temp_dir = init_temp_repo("push-scan-no-remote");
+    let key = fake_keys::AWS;
+    std::fs::write(
+        temp_dir.join("keys.rs"),
+        format!("const K: &str = \"{}\";\n", key),
+    )
+    .unwrap();
+    git(&temp_dir, &["add", "keys.rs"]);
+    git(&temp_dir, &["commit", "-q", "-m", "Ancient keys"]);
+
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--force", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    server.join().unwrap();
+
+    // Nowhere to push to, so the old commit is none of our business
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(!stderr.contains("AWS access key"), "got: {}", stderr);
+    assert!(stderr.contains("Push failed"), "got: {}", stderr);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_push_refused_when_older_commit_has_a_secret() {
+    let temp_dir = init_temp_repo("push-scan");
+    let remote = temp_dir.with_extension("remote.git");
+    std::fs::remove_dir_all(&remote).ok();
+    git(
+        &temp_dir,
+        &["init", "-q", "--bare", remote.to_str().unwrap()],
+    );
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+    git(&temp_dir, &["add", "main.rs"]);
+    git(&temp_dir, &["commit", "-q", "-m", "Initial"]);
+    git(
+        &temp_dir,
+        &["remote", "add", "origin", remote.to_str().unwrap()],
+    );
+    git(&temp_dir, &["push", "-q", "-u", "origin", "HEAD"]);
+    let pushed = git(&temp_dir, &["rev-parse", "@{upstream}"]);
+
+    // Committed by hand, never seen by noob-commit
+    let key = fake_keys::AWS;
+    std::fs::write(
+        temp_dir.join("keys.rs"),
+        format!("const K: &str = \"{}\";\n", key),
+    )
+    .unwrap();
+    git(&temp_dir, &["add", "keys.rs"]);
+    git(&temp_dir, &["commit", "-q", "-m", "Add keys"]);
+    let sha = git(&temp_dir, &["rev-parse", "--short=8", "HEAD"]);
+
+    std::fs::write(
+        temp_dir.join("main.rs"),
+        "fn main() { println!(\"hi\"); }\n",
+    )
+    .unwrap();
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--force", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4), "got: {}", stderr);
+    assert!(
+        stderr.contains(&format!("AWS access key in {} keys.rs:1", sha.trim())),
+        "got: {}",
+        stderr
+    );
+    assert!(!stderr.contains(key), "printed the whole key: {}", stderr);
+    assert_eq!(
+        git(&temp_dir, &["rev-list", "--count", "HEAD"]).trim(),
+        "3",
+        "didn't commit"
+    );
+    assert_eq!(
+        git(&temp_dir, &["rev-parse", "@{upstream}"]),
+        pushed,
+        "pushed anyway"
+    );
+    let remote_head = git(&remote, &["rev-list", "--count", "HEAD"]);
+    assert_eq!(remote_head.trim(), "1");
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+    std::fs::remove_dir_all(&remote).ok();
+}
+
+#[test]
+fn test_allowlist_lets_fake_secrets_through_and_says_why() {
+    let temp_dir = init_temp_repo("allowlist");
+    let aws = fake_keys::AWS;
+    let github = fake_keys::GITHUB;
+    let stripe = fake_keys::STRIPE;
+    std::fs::create_dir_all(temp_dir.join("tests/fixtures")).unwrap();
+    std::fs::create_dir_all(temp_dir.join("src")).unwrap();
+    std::fs::write(
+        temp_dir.join("tests/fixtures/keys.json"),
+        format!("{{\"key\": \"{}\"}}\n", aws),
+    )
+    .unwrap();
+    std::fs::write(temp_dir.join("tests/fixtures/.env"), "TOKEN=fake\n").unwrap();
+    std::fs::write(
+        temp_dir.join("src/config.rs"),
+        format!("const T: &str = \"{}\"; // noob-commit:allow\n", github),
+    )
+    .unwrap();
+    std::fs::write(temp_dir.join("docs.md"), format!("STRIPE={}\n", stripe)).unwrap();
+    let fingerprint = noob_commit::secrets::Finding {
+        kind: noob_commit::secrets::SecretKind::StripeKey,
+        path: "docs.md".to_string(),
+        line: 1,
+        secret: stripe.to_string(),
+        allowed_inline: false,
+    }
+    .fingerprint();
+    std::fs::write(
+        temp_dir.join(".noob-commit-allow"),
+        format!(
+            "tests/fixtures/\n# the docs example\nfingerprint:{}\n",
+            fingerprint
+        ),
+    )
+    .unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args(["--dry-run", "--provider", "ollama", "--api-base", &url])
+        .output()
+        .expect("Failed to execute command");
+    server.join().unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    for expected in [
+        "Including tests/fixtures/.env anyway (.noob-commit-allow:1 'tests/fixtures/')",
+        "Allowing AWS access key in tests/fixtures/keys.json:1 (.noob-commit-allow:1 'tests/fixtures/')",
+        "Allowing GitHub token in src/config.rs:1 (inline noob-commit:allow)",
+        &format!(
+            "Allowing Stripe key in docs.md:1 (.noob-commit-allow:3 'fingerprint:{}')",
+            fingerprint
+        ),
+    ] {
+        assert!(stderr.contains(expected), "missing {:?} in: {}", expected, stderr);
+    }
+
+    // Without its entry the same key is stopped, with the fingerprint to allow it
+    std::fs::write(temp_dir.join(".noob-commit-allow"), "tests/fixtures/\n").unwrap();
+    let output = noob_commit(&temp_dir)
+        .args([
+            "--dry-run",
+            "--provider",
+            "ollama",
+            "--api-base",
+            "http://127.0.0.1:9",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4), "got: {}", stderr);
+    assert!(
+        stderr.contains(&format!("fingerprint {}", fingerprint)),
+        "got: {}",
+        stderr
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_filtered_junk_is_added_to_gitignore_and_committed() {
+    let temp_dir = init_temp_repo("auto-ignore");
+    std::fs::create_dir_all(temp_dir.join("node_modules/pad")).unwrap();
+    std::fs::create_dir_all(temp_dir.join("pkg/__pycache__")).unwrap();
+    std::fs::write(temp_dir.join("node_modules/pad/index.js"), "1;\n").unwrap();
+    std::fs::write(temp_dir.join("pkg/__pycache__/m.cpython-312.pyc"), "x").unwrap();
+    std::fs::write(temp_dir.join(".DS_Store"), "x").unwrap();
+    std::fs::write(temp_dir.join(".gitignore"), "target/\n").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let (url, server) = mock_ollama_once();
+        let output = noob_commit(&temp_dir)
+            .args([
+                "--force",
+                "--no-push",
+                "--provider",
+                "ollama",
+                "--api-base",
+                &url,
+            ])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        server.join().unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "got: {}", stderr);
+        stderr
+    };
+
+    run(&[]);
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap(),
+        "target/\n\n# Added by noob-commit\n.DS_Store\nnode_modules/\n__pycache__/\n"
+    );
+    assert_eq!(
+        git(&temp_dir, &["show", "--name-only", "--format=", "HEAD"]),
+        ".gitignore\nmain.rs\n"
+    );
+    assert_eq!(
+        git(&temp_dir, &["status", "--porcelain"]),
+        "",
+        "junk still shows up"
+    );
+
+    // Just for this clone, nothing to commit in .gitignore
+    std::fs::create_dir_all(temp_dir.join(".venv")).unwrap();
+    std::fs::write(temp_dir.join(".venv/lib.py"), "x = 1\n").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() { main() }\n").unwrap();
+    let stderr = run(&["--local-ignore"]);
+    assert!(stderr.contains("Added 1 pattern(s)"), "got: {}", stderr);
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".git/info/exclude"))
+            .unwrap()
+            .lines()
+            .last(),
+        Some(".venv/")
+    );
+    assert_eq!(
+        git(&temp_dir, &["show", "--name-only", "--format=", "HEAD"]),
+        "main.rs\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_placeholders_are_never_offered_for_gitignore() {
+    let temp_dir = init_temp_repo("auto-ignore-gitkeep");
+    std::fs::create_dir_all(temp_dir.join("logs")).unwrap();
+    std::fs::write(temp_dir.join("logs/.gitkeep"), "").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+    let (url, server) = mock_ollama_once();
+    let output = noob_commit(&temp_dir)
+        .args([
+            "--force",
+            "--no-push",
+            "--provider",
+            "ollama",
+            "--api-base",
+            &url,
+        ])
+        .output()
+        .expect("Failed to execute command");
+    server.join().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(!stderr.contains("Not ignored yet"), "got: {}", stderr);
+    assert!(!temp_dir.join(".gitignore").exists());
+    assert_eq!(
+        git(&temp_dir, &["show", "--name-only", "--format=", "HEAD"]),
+        "main.rs\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_gitignore_is_left_alone_when_the_ai_fails() {
+    let temp_dir = init_temp_repo("auto-ignore-ai-fails");
+    std::fs::create_dir_all(temp_dir.join("node_modules/pad")).unwrap();
+    std::fs::write(temp_dir.join("node_modules/pad/index.js"), "1;\n").unwrap();
+    std::fs::write(temp_dir.join(".gitignore"), "target/\n").unwrap();
+    std::fs::write(temp_dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+    for args in [&["--local-ignore"][..], &[]] {
+        let output = noob_commit(&temp_dir)
+            .args([
+                "--force",
+                "--no-push",
+                "--provider",
+                "ollama",
+                "--api-base",
+                "http://127.0.0.1:9",
+            ])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(3), "got: {}", stderr);
+    }
+
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap(),
+        "target/\n"
+    );
+    let exclude = std::fs::read_to_string(temp_dir.join(".git/info/exclude")).unwrap_or_default();
+    assert!(!exclude.contains("node_modules/"), "got: {}", exclude);
+    assert_eq!(git(&temp_dir, &["diff", "--staged", "--name-only"]), "");
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_init_dry_run_leaves_gitignore_alone() {
+    let temp_dir = init_temp_repo("init-dry-run");
+    std::fs::write(temp_dir.join("go.mod"), "module example.com/app\n").unwrap();
+    std::fs::write(temp_dir.join(".gitignore"), "/notes.txt\n").unwrap();
+
+    let output = noob_commit(&temp_dir)
+        .args(["init", "--dry-run"])
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "got: {}", stderr);
+    assert!(stderr.contains("Would add to"), "got: {}", stderr);
+    assert!(stderr.contains("vendor/"), "got: {}", stderr);
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap(),
+        "/notes.txt\n"
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}
+
+#[test]
+fn test_init_writes_a_starter_gitignore_merged_with_yours() {
+    let temp_dir = init_temp_repo("init-gitignore");
+    std::fs::create_dir_all(temp_dir.join("web")).unwrap();
+    std::fs::write(temp_dir.join("Cargo.toml"), "[package]\n").unwrap();
+    std::fs::write(temp_dir.join("web/package.json"), "{}\n").unwrap();
+    std::fs::write(temp_dir.join(".gitignore"), "target/\n/notes.txt").unwrap();
+
+    let init = || {
+        let output = noob_commit(&temp_dir.join("web"))
+            .arg("init")
+            .output()
+            .expect("Failed to execute command");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "got: {}", stderr);
+        stderr
+    };
+
+    let stderr = init();
+    assert!(stderr.contains("Looks like Rust + Node"), "got: {}", stderr);
+    let gitignore = std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap();
+    assert!(
+        gitignore.starts_with("target/\n/notes.txt\n\n# Added by noob-commit\n# Secrets\n.env\n"),
+        "got: {}",
+        gitignore
+    );
+    assert!(
+        gitignore.contains("\n# Node\nnode_modules/\n"),
+        "got: {}",
+        gitignore
+    );
+    // Rust only needs target/, which was there already
+    assert!(!gitignore.contains("# Rust"), "got: {}", gitignore);
+    assert_eq!(gitignore.matches("target/").count(), 1);
+
+    let stderr = init();
+    assert!(stderr.contains("already has everything"), "got: {}", stderr);
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.join(".gitignore")).unwrap(),
+        gitignore
+    );
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+}